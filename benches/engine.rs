@@ -0,0 +1,50 @@
+use std::hint::black_box;
+
+use criterion::{BatchSize, BenchmarkId, Criterion, criterion_group, criterion_main};
+use Kyun2Stop::dsp::engine::{EngineParams, TapeStopEngine};
+
+const SAMPLE_RATE: f32 = 48_000.0;
+const FRAMES: usize = 4_096;
+
+/**
+ * 停止ランプ中の処理速度を SIMD とスカラーで比べる
+ */
+fn bench_stop_ramp(c: &mut Criterion) {
+    let mut group = c.benchmark_group("stop_ramp");
+
+    for channels in [2usize, 8] {
+        for simd in [true, false] {
+            let label = if simd { "simd" } else { "scalar" };
+            group.bench_with_input(BenchmarkId::new(label, channels), &channels, |b, &channels| {
+                let params = EngineParams {
+                    trigger: true,
+                    stop_time_sec: 2.0,
+                    ..EngineParams::default()
+                };
+
+                let input: Vec<f32> = (0..channels).map(|ch| 0.1 * (ch + 1) as f32).collect();
+                let mut output = vec![0.0f32; channels];
+                // 毎回新しいエンジンで、停止ランプの先頭 FRAMES サンプルを測る
+                b.iter_batched_ref(
+                    || {
+                        let mut engine = TapeStopEngine::<f32>::new(SAMPLE_RATE, 3.0, channels);
+                        engine.set_simd(simd);
+                        engine
+                    },
+                    |engine| {
+                        for _ in 0..FRAMES {
+                            engine.process(black_box(&input), &mut output, &params);
+                        }
+                        black_box(&output);
+                    },
+                    BatchSize::LargeInput,
+                );
+            });
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_stop_ramp);
+criterion_main!(benches);
\ No newline at end of file
@@ -7,7 +7,7 @@
 
 class K2sEngine {
 public:
-    explicit K2sEngine(const K2sConfig& config) : handle_(k2s_create(config)) {
+    explicit K2sEngine(const K2sConfig& config) : handle_(k2s_create(&config)) {
         if (handle_ == nullptr) {
             throw std::runtime_error("k2s_create failed");
         }
@@ -37,12 +37,24 @@ public:
         return *this;
     }
 
+    bool prepare(const K2sConfig& config) {
+        return k2s_prepare(handle_, &config);
+    }
+
     bool reset() {
         return k2s_reset(handle_);
     }
 
+    uint32_t latency() const {
+        return k2s_get_latency(handle_);
+    }
+
     bool processInterleavedF32(const float* input, float* output, size_t frames, const K2sProcessParams& params) {
-        return k2s_process_interleaved_f32(handle_, input, output, frames, params);
+        return k2s_process_interleaved_f32(handle_, input, output, frames, &params);
+    }
+
+    bool processInterleavedF64(const double* input, double* output, size_t frames, const K2sProcessParams& params) {
+        return k2s_process_interleaved_f64(handle_, input, output, frames, &params);
     }
 
 private:
@@ -10,6 +10,7 @@ int main() {
     constexpr size_t frames = 256;
 
     K2sConfig config{};
+    config.struct_size = sizeof(K2sConfig);
     config.sample_rate = 48000.0f;
     config.max_seconds = 3.0f;
     config.channels = channels;
@@ -28,6 +29,7 @@ int main() {
         }
 
         K2sProcessParams params{};
+        params.struct_size = sizeof(K2sProcessParams);
         params.trigger = true;
         params.stop_time_sec = 0.5f;
         params.start_time_sec = 0.5f;
@@ -36,6 +38,9 @@ int main() {
         params.sync_beat = K2S_SYNC_QUARTER;
         params.bpm = 120.0;
         params.enable_filter = true;
+        params.mix = 1.0f;
+        params.output_gain = 1.0f;
+        params.enable_limiter = true;
 
         if (!engine.processInterleavedF32(input.data(), output.data(), frames, params)) {
             std::cerr << "k2s_process_interleaved_f32 failed" << std::endl;
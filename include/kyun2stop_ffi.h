@@ -20,9 +20,12 @@ extern "C" {
 #endif
 
 typedef struct K2sConfig {
+    uint32_t struct_size;
     float sample_rate;
     float max_seconds;
     uint32_t channels;
+    uint32_t oversampling;
+    bool double_precision;
 } K2sConfig;
 
 typedef enum K2sCurve {
@@ -30,6 +33,7 @@ typedef enum K2sCurve {
     K2S_CURVE_SMOOTH = 1,
     K2S_CURVE_SLOW_START = 2,
     K2S_CURVE_QUICK_CUT = 3,
+    K2S_CURVE_PITCH_LINEAR = 4,
 } K2sCurve;
 
 typedef enum K2sSyncBeat {
@@ -40,7 +44,19 @@ typedef enum K2sSyncBeat {
     K2S_SYNC_TWO_BARS = 4,
 } K2sSyncBeat;
 
+typedef enum K2sHoldDivision {
+    K2S_HOLD_THIRTY_SECOND = 0,
+    K2S_HOLD_SIXTEENTH = 1,
+    K2S_HOLD_EIGHT = 2,
+    K2S_HOLD_QUARTER = 3,
+} K2sHoldDivision;
+
+/**
+ * 1ブロックぶんの処理パラメータ
+ * 0 で初期化しただけ（K2sProcessParams{}）だと mix = 0 でドライのみ、output_gain = 0 で無音になるので、使う値はすべて設定すること
+ */
 typedef struct K2sProcessParams {
+    uint32_t struct_size;
     bool trigger;
     float stop_time_sec;
     float start_time_sec;
@@ -49,19 +65,61 @@ typedef struct K2sProcessParams {
     K2sSyncBeat sync_beat;
     double bpm;
     bool enable_filter;
+    float mix;
+    float output_gain;
+    bool enable_limiter;
+    bool enable_wow;
+    float wow_rate;
+    float wow_depth;
+    float wow_instability;
+    bool enable_saturation;
+    float saturation_drive;
+    float saturation_bias;
+    float saturation_mix;
+    bool saturation_link;
+    bool enable_decimate;
+    float decimate_amount;
+    bool enable_bitcrush;
+    float bitcrush_amount;
+    bool lofi_anti_alias;
+    float stereo_spread;
+    bool enable_width;
+    float width_at_stop;
+    float width_response;
+    bool enable_noise;
+    float hiss_level;
+    float crackle_level;
+    float motor_level;
+    bool enable_hold;
+    K2sHoldDivision hold_division;
+    float hold_crossfade;
+    uint32_t hold_repeats;
+    float pitch_floor_st;
+    bool land_on_beat;
 } K2sProcessParams;
 
 typedef struct K2sOpaqueHandle K2sOpaqueHandle;
 
-K2S_API K2sOpaqueHandle* k2s_create(K2sConfig config);
+K2S_API K2sOpaqueHandle* k2s_create(const K2sConfig* config);
 K2S_API void k2s_destroy(K2sOpaqueHandle* handle);
+K2S_API bool k2s_prepare(K2sOpaqueHandle* handle, const K2sConfig* config);
 K2S_API bool k2s_reset(K2sOpaqueHandle* handle);
+K2S_API uint32_t k2s_get_latency(const K2sOpaqueHandle* handle);
+K2S_API float k2s_required_seconds(double min_bpm);
+K2S_API bool k2s_is_clamped(const K2sOpaqueHandle* handle);
 K2S_API bool k2s_process_interleaved_f32(
     K2sOpaqueHandle* handle,
     const float* input,
     float* output,
     size_t frames,
-    K2sProcessParams params
+    const K2sProcessParams* params
+);
+K2S_API bool k2s_process_interleaved_f64(
+    K2sOpaqueHandle* handle,
+    const double* input,
+    double* output,
+    size_t frames,
+    const K2sProcessParams* params
 );
 
 #ifdef __cplusplus
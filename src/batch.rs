@@ -0,0 +1,226 @@
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+
+use rand::seq::IndexedRandom;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::Serialize;
+
+use crate::dsp::curve::TapeCurve;
+use crate::dsp::engine::MAX_RAMP_SEC;
+use crate::dsp::sync::SyncBeat;
+use crate::render::{render, Audio, OutputFormat, RenderError, Timeline, TimelineEvent};
+
+// 出力フォルダに書き出すマニフェストのファイル名
+pub const MANIFEST_NAME: &str = "manifest.json";
+// 乱数で選ぶ停止/再始動時間の下限（秒）
+const MIN_RAMP_SEC: f32 = 0.1;
+// 停止位置を選ぶ範囲（素材の長さに対する割合）
+const STOP_POSITION: std::ops::Range<f64> = 0.1..0.6;
+// 最後まで止めたままにせず、再始動する確率
+const RESTART_PROBABILITY: f64 = 0.7;
+// 停止時間の代わりにBPM同期を使う確率
+const SYNC_PROBABILITY: f64 = 0.25;
+
+/**
+ * フォルダ一括処理の設定
+ */
+#[derive(Clone, Copy, Debug)]
+pub struct BatchOptions {
+    pub variations: usize,    // 1ファイルあたりのバリエーション数
+    pub seed: u64,            // 乱数のシード
+    pub bpm: f64,             // BPM同期に使うテンポ
+    pub format: OutputFormat, // 書き出すWAVの形式
+}
+
+/**
+ * マニフェストの1行（1つの出力ファイルとそれを作った設定）
+ */
+#[derive(Clone, Debug, Serialize)]
+pub struct ManifestEntry {
+    pub source: String,     // 元のファイル名
+    pub output: String,     // 出力ファイル名
+    pub variation: usize,   // バリエーション番号（1から）
+    pub seed: u64,          // このバリエーションのシード
+    pub timeline: Timeline, // 実際に使ったタイムライン（そのままタイムラインファイルとして使える）
+}
+
+/**
+ * バリエーションごとのシード
+ * ファイル名と番号から決めるので、フォルダにファイルが増えても他の結果は変わらない
+ */
+fn variation_seed(seed: u64, name: &str, variation: usize) -> u64 {
+    // FNV-1a
+    let mut hash = 0xcbf2_9ce4_8422_2325 ^ seed;
+    for byte in name.bytes().chain((variation as u64).to_le_bytes()) {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
+
+/**
+ * 乱数で停止位置・カーブ・時間を決めたテープストップを1回入れたタイムラインを作る
+ * 素材が短すぎて停止と再始動が重なるなど、レンダリングできないタイムラインになったときはエラーを返す
+ * - seed 乱数のシード（同じシードからは同じタイムラインになる）
+ * - duration 素材の長さ（秒）
+ * - bpm BPM同期に使うテンポ
+ */
+pub fn random_timeline(seed: u64, duration: f64, bpm: f64) -> Result<Timeline, RenderError> {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+
+    let stop = duration * rng.random_range(STOP_POSITION);
+    let start = rng
+        .random_bool(RESTART_PROBABILITY)
+        .then(|| stop + (duration - stop) * rng.random_range(0.2..0.8));
+    let curve = *TapeCurve::ALL.choose(&mut rng).unwrap();
+    let stop_time = rng.random_range(MIN_RAMP_SEC..=MAX_RAMP_SEC);
+    let start_time = rng.random_range(MIN_RAMP_SEC..=MAX_RAMP_SEC);
+    let sync = rng
+        .random_bool(SYNC_PROBABILITY)
+        .then(|| *SyncBeat::ALL.choose(&mut rng).unwrap());
+    let land_on_beat = sync.is_some() && rng.random_bool(0.5);
+
+    Timeline {
+        bpm,
+        tempo: Vec::new(),
+        events: vec![TimelineEvent {
+            stop,
+            start,
+            curve: Some(curve),
+            stop_time: Some(stop_time),
+            start_time: Some(start_time),
+            sync,
+            land_on_beat,
+        }],
+    }
+    .validated()
+}
+
+/**
+ * フォルダ内のWAVファイルそれぞれに、乱数で決めたテープストップを掛けたバリエーションを書き出す
+ * 出力は <名前>_v01.wav のように番号を付け、設定は manifest.json にまとめる
+ */
+pub fn process_directory(input_dir: &Path, output_dir: &Path, options: &BatchOptions) -> Result<Vec<ManifestEntry>, RenderError> {
+    // 結果が実行ごとに変わらないよう、名前順に処理する
+    let mut sources = Vec::new();
+    for entry in fs::read_dir(input_dir)? {
+        let path = entry?.path();
+        let is_wav = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("wav"));
+        if path.is_file() && is_wav {
+            sources.push(path);
+        }
+    }
+    sources.sort();
+    fs::create_dir_all(output_dir)?;
+
+    let mut manifest = Vec::new();
+    for source in &sources {
+        let audio = Audio::read(source)?;
+        let duration = audio.frames() as f64 / audio.sample_rate as f64;
+        let name = source.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        let stem = source.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+
+        for variation in 1..=options.variations {
+            let seed = variation_seed(options.seed, &name, variation);
+            // どのファイルが原因か分かるよう、ファイル名を付けて返す
+            let timeline = random_timeline(seed, duration, options.bpm).map_err(|err| match err {
+                RenderError::Timeline(message) => RenderError::Timeline(format!("{name}: {message}")),
+                err => err,
+            })?;
+            let output = format!("{stem}_v{variation:02}.wav");
+            render(&audio, &timeline).write(&output_dir.join(&output), options.format)?;
+
+            manifest.push(ManifestEntry {
+                source: name.clone(),
+                output,
+                variation,
+                seed,
+                timeline,
+            });
+        }
+    }
+
+    let file = File::create(output_dir.join(MANIFEST_NAME))?;
+    serde_json::to_writer_pretty(file, &manifest).map_err(io::Error::from)?;
+    Ok(manifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn random_timeline_is_reproducible_and_valid() {
+        for seed in 0..32 {
+            let timeline = random_timeline(seed, 4.0, 120.0).unwrap();
+            assert_eq!(
+                serde_json::to_string(&timeline).unwrap(),
+                serde_json::to_string(&random_timeline(seed, 4.0, 120.0).unwrap()).unwrap()
+            );
+
+            let event = &timeline.events[0];
+            assert!((0.4..2.4).contains(&event.stop));
+            assert!(event.start.is_none_or(|start| start > event.stop && start < 4.0));
+        }
+        let stop = |seed| random_timeline(seed, 4.0, 120.0).unwrap().events[0].stop;
+        assert_ne!(stop(1), stop(2));
+    }
+
+    #[test]
+    fn empty_input_is_reported_instead_of_rendered() {
+        // 長さ 0 の素材では停止と再始動が同じ時刻になる（再始動しないシードでは停止だけの正しいタイムラインになる）
+        let results: Vec<_> = (0..32).map(|seed| random_timeline(seed, 0.0, 120.0)).collect();
+        assert!(results.iter().any(|result| matches!(result, Err(RenderError::Timeline(_)))));
+        assert!(results.iter().flatten().all(|timeline| timeline.events[0].start.is_none()));
+
+        let root = std::env::temp_dir().join(format!("k2s_batch_empty_{}", std::process::id()));
+        let input_dir = root.join("in");
+        fs::create_dir_all(&input_dir).unwrap();
+        let audio = Audio { samples: Vec::new(), channels: 1, sample_rate: 4_000 };
+        audio.write(&input_dir.join("empty.wav"), OutputFormat::Float32).unwrap();
+
+        let options = BatchOptions {
+            variations: 8,
+            seed: 0,
+            bpm: 120.0,
+            format: OutputFormat::Int16,
+        };
+        let result = process_directory(&input_dir, &root.join("out"), &options);
+        fs::remove_dir_all(&root).unwrap();
+        assert!(matches!(result, Err(RenderError::Timeline(message)) if message.starts_with("empty.wav: ")));
+    }
+
+    #[test]
+    fn directory_gets_variations_and_a_manifest() {
+        let root = std::env::temp_dir().join(format!("k2s_batch_{}", std::process::id()));
+        let (input_dir, output_dir) = (root.join("in"), root.join("out"));
+        fs::create_dir_all(&input_dir).unwrap();
+        for name in ["a.wav", "b.wav"] {
+            let samples = (0..4_000).map(|i| (i as f32 * 0.05).sin() * 0.5).collect();
+            let audio = Audio { samples, channels: 1, sample_rate: 4_000 };
+            audio.write(&input_dir.join(name), OutputFormat::Float32).unwrap();
+        }
+        fs::write(input_dir.join("notes.txt"), "not audio").unwrap();
+
+        let options = BatchOptions {
+            variations: 3,
+            seed: 42,
+            bpm: 120.0,
+            format: OutputFormat::Int16,
+        };
+        let manifest = process_directory(&input_dir, &output_dir, &options).unwrap();
+        let again = process_directory(&input_dir, &root.join("again"), &options).unwrap();
+        let written = fs::read_to_string(output_dir.join(MANIFEST_NAME)).unwrap();
+        let outputs: Vec<_> = manifest.iter().map(|entry| entry.output.as_str()).collect();
+        let all_written = outputs.iter().all(|output| output_dir.join(output).is_file());
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(outputs, ["a_v01.wav", "a_v02.wav", "a_v03.wav", "b_v01.wav", "b_v02.wav", "b_v03.wav"]);
+        assert!(all_written);
+        // 同じシードなら同じ設定になり、マニフェストにすべて記録される
+        assert_eq!(serde_json::to_string_pretty(&manifest).unwrap(), written);
+        assert_eq!(written, serde_json::to_string_pretty(&again).unwrap());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,178 @@
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use serde::de::{DeserializeOwned, IntoDeserializer};
+use serde::de::value::Error as ValueError;
+use Kyun2Stop::batch::{self, BatchOptions};
+use Kyun2Stop::midi::{self, MidiTrigger};
+use Kyun2Stop::render::{render, Audio, OutputFormat, RenderError, Timeline};
+
+const USAGE: &str = "\
+Render tape stops into a WAV file from a JSON/TOML timeline or a Standard MIDI File
+
+Usage: kyun2stop-render <INPUT> <OUTPUT> --timeline <FILE> [OPTIONS]
+       kyun2stop-render --batch <INPUT_DIR> <OUTPUT_DIR> [--variations <N>] [--seed <SEED>] [OPTIONS]
+
+Options:
+  -t, --timeline <FILE>  Timeline file (.json, .toml, .mid or .midi)
+  -f, --format <FORMAT>  Output sample format: 16, 24 or float [default: 24]
+  -h, --help             Print help
+
+Batch options (render every WAV file in a directory with random tape stops; an error without --batch):
+  --batch                Treat INPUT and OUTPUT as directories
+  --variations <N>       Variations per file [default: 4]
+  --seed <SEED>          Random seed [default: 0]
+  --bpm <BPM>            Tempo for synced stops [default: 120]
+
+MIDI options (only for .mid/.midi timelines; an error otherwise):
+  --channel <1-16>       Only notes on this channel trigger the tape stop [default: all]
+  --note <0-127>         Only this note triggers the tape stop [default: all]
+  --curve <CURVE>        linear, smooth, slow_start, quick_cut or pitch_linear
+  --sync <BEAT>          eight, quarter, half, one_bar or two_bars (follows the SMF tempo map)
+  --stop-time <SEC>      Stop time in seconds
+  --start-time <SEC>     Start time in seconds
+  --land-on-beat         Stop exactly on the synced beat";
+
+// .mid/.midi のタイムラインでだけ使うオプション
+const MIDI_OPTIONS: &[&str] = &["--channel", "--note", "--curve", "--sync", "--stop-time", "--start-time", "--land-on-beat"];
+// --batch でだけ使うオプション
+const BATCH_OPTIONS: &[&str] = &["--variations", "--seed", "--bpm"];
+
+// 1ファイルをタイムラインで書き出すか、フォルダを一括処理するか
+enum Mode {
+    Render { timeline: PathBuf, format: OutputFormat, midi: MidiTrigger },
+    Batch(BatchOptions),
+}
+
+// コマンドライン引数
+struct Args {
+    input: PathBuf,
+    output: PathBuf,
+    mode: Mode,
+}
+
+/**
+ * カーブや拍数をタイムラインファイルと同じ名前で読む
+ */
+fn parse_name<T: DeserializeOwned>(name: &str) -> Result<T, String> {
+    T::deserialize(IntoDeserializer::<ValueError>::into_deserializer(name)).map_err(|err| err.to_string())
+}
+
+fn parse_number<T: std::str::FromStr>(option: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("invalid value `{value}` for {option}"))
+}
+
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut paths = Vec::new();
+        let mut timeline = None;
+        let mut format = OutputFormat::Int24;
+        let mut midi = MidiTrigger::default();
+        let mut batch = false;
+        let (mut variations, mut seed, mut bpm) = (4, 0, 120.0f64);
+        // 使わないモードで指定されたオプションを黙って捨てないよう、最初に見つかったものを覚えておく
+        let (mut midi_option, mut batch_option) = (None, None);
+        while let Some(arg) = args.next() {
+            if MIDI_OPTIONS.contains(&arg.as_str()) {
+                midi_option.get_or_insert_with(|| arg.clone());
+            }
+            if BATCH_OPTIONS.contains(&arg.as_str()) {
+                batch_option.get_or_insert_with(|| arg.clone());
+            }
+            let mut value = || args.next().ok_or(format!("{arg} needs a value"));
+            match arg.as_str() {
+                "-t" | "--timeline" => timeline = Some(value()?),
+                "-f" | "--format" => format = value()?.parse()?,
+                "--channel" => match parse_number::<u8>(&arg, &value()?)? {
+                    channel @ 1..=16 => midi.channel = Some(channel - 1),
+                    _ => return Err("--channel must be between 1 and 16".into()),
+                },
+                "--note" => match parse_number::<u8>(&arg, &value()?)? {
+                    note @ 0..=127 => midi.note = Some(note),
+                    _ => return Err("--note must be between 0 and 127".into()),
+                },
+                "--curve" => midi.event.curve = Some(parse_name(&value()?)?),
+                "--sync" => midi.event.sync = Some(parse_name(&value()?)?),
+                "--stop-time" => midi.event.stop_time = Some(parse_number(&arg, &value()?)?),
+                "--start-time" => midi.event.start_time = Some(parse_number(&arg, &value()?)?),
+                "--land-on-beat" => midi.event.land_on_beat = true,
+                "--batch" => batch = true,
+                "--variations" => variations = parse_number(&arg, &value()?)?,
+                "--seed" => seed = parse_number(&arg, &value()?)?,
+                "--bpm" => bpm = parse_number(&arg, &value()?)?,
+                _ if arg.starts_with('-') => return Err(format!("unknown option `{arg}`")),
+                _ => paths.push(PathBuf::from(arg)),
+            }
+        }
+
+        let [input, output] = <[PathBuf; 2]>::try_from(paths).map_err(|_| "expected an input and an output")?;
+        let mode = if batch {
+            if let Some(option) = midi_option {
+                return Err(format!("{option} only applies to .mid/.midi timelines"));
+            }
+            if timeline.is_some() {
+                return Err("--timeline cannot be used with --batch".into());
+            }
+            if bpm.is_nan() || bpm <= 0.0 {
+                return Err("--bpm must be positive".into());
+            }
+            Mode::Batch(BatchOptions { variations, seed, bpm, format })
+        } else {
+            if let Some(option) = batch_option {
+                return Err(format!("{option} only applies with --batch"));
+            }
+            let timeline: PathBuf = timeline.ok_or("--timeline is required")?.into();
+            if let Some(option) = midi_option.filter(|_| !is_midi(&timeline)) {
+                return Err(format!("{option} only applies to .mid/.midi timelines"));
+            }
+            Mode::Render { timeline, format, midi }
+        };
+        Ok(Self { input, output, mode })
+    }
+}
+
+fn is_midi(path: &Path) -> bool {
+    matches!(path.extension().and_then(|ext| ext.to_str()), Some("mid" | "midi"))
+}
+
+fn run(args: &Args) -> Result<(), RenderError> {
+    match &args.mode {
+        Mode::Render { timeline, format, midi } => {
+            let input = Audio::read(&args.input)?;
+            let timeline = if is_midi(timeline) {
+                midi::load_timeline(timeline, midi)?
+            } else {
+                Timeline::load(timeline)?
+            };
+            render(&input, &timeline).write(&args.output, *format)
+        }
+        Mode::Batch(options) => {
+            let manifest = batch::process_directory(&args.input, &args.output, options)?;
+            println!("Wrote {} files and {}", manifest.len(), batch::MANIFEST_NAME);
+            Ok(())
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{USAGE}");
+        return ExitCode::SUCCESS;
+    }
+
+    let args = match Args::parse(args.into_iter()) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("kyun2stop-render: {err}\n\n{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("kyun2stop-render: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
\ No newline at end of file
@@ -1,31 +1,53 @@
-pub struct DelayLine {
-    data: Vec<f32>,
+use std::ops::{Add, Mul, Sub};
+
+use super::sample::{Sample, SampleLanes, LANES};
+
+pub struct DelayLine<S: Sample = f32> {
+    data: Vec<S>,
     mask: usize,
 }
 
-impl DelayLine {
+impl<S: Sample> DelayLine<S> {
     pub fn new(max_seconds: f32, sample_rate: f32) -> Self {
         let size = (max_seconds * sample_rate) as usize;
         let power_of_two_size = size.next_power_of_two();
 
         Self {
-            data: vec![0.0; power_of_two_size],
+            data: vec![S::ZERO; power_of_two_size],
             mask: power_of_two_size - 1,
         }
     }
 
+    /**
+    * 容量を変更して中身を消去（リアルタイムスレッドから呼ばないこと）
+    * 今の確保量で足りるときは確保し直さない
+    */
+    pub fn resize(&mut self, max_seconds: f32, sample_rate: f32) {
+        let size = ((max_seconds * sample_rate) as usize).next_power_of_two();
+        self.data.clear();
+        self.data.resize(size, S::ZERO);
+        self.mask = size - 1;
+    }
+
+    /**
+    * 保持できるサンプル数
+    */
+    pub fn capacity(&self) -> usize {
+        self.data.len()
+    }
+
     /**
     * バッファをリセット
     */
     pub fn reset(&mut self) {
-        self.data.fill(0.0);
+        self.data.fill(S::ZERO);
     }
 
     /**
     * 新しいサンプルをバッファに追加
     */
     #[inline]
-    pub fn write(&mut self, index: usize, value: f32) {
+    pub fn write(&mut self, index: usize, value: S) {
         self.data[index & self.mask] = value;
     }
 
@@ -33,13 +55,45 @@ impl DelayLine {
     * 現在の書き込み位置から指定された遅延サンプル数だけ前のサンプルを取得
     */
     #[inline]
-    pub fn read(&self, index: f64) -> f32 {
-        let len = self.data.len();
+    pub fn read(&self, index: f64) -> S {
+        let (points, frac) = self.points(index);
+        hermite(points, frac, S::from_f32)
+    }
+
+    /**
+    * 複数のバッファの同じ位置をまとめて読む（SIMD）
+    * buffers は LANES 本まで。足りないレーンは 0 になる
+    */
+    #[inline]
+    pub fn read_lanes(buffers: &[DelayLine<S>], index: f64) -> S::Lanes {
+        let mut columns = [[S::ZERO; LANES]; 4];
+        let mut frac = S::ZERO;
+        for (lane, buffer) in buffers.iter().enumerate().take(LANES) {
+            let (points, f) = buffer.points(index);
+            for (column, point) in columns.iter_mut().zip(points) {
+                column[lane] = point;
+            }
+            frac = f;
+        }
+
+        hermite(
+            columns.map(S::Lanes::from_array),
+            S::Lanes::splat(frac),
+            |value| S::Lanes::splat(S::from_f32(value)),
+        )
+    }
+
+    /**
+    * 補間に使う4点と小数部を取得
+    */
+    #[inline]
+    fn points(&self, index: f64) -> ([S; 4], S) {
         let mask = self.mask;
 
-        // 整数部と小数部
-        let idx_i = index.floor() as usize;
-        let frac = (index - idx_i as f64) as f32;
+        // 整数部と小数部（負の位置はバッファの末尾側に折り返す）
+        let floor = index.floor();
+        let idx_i = floor as i64 as usize;
+        let frac = S::from_f64(index - floor);
 
         // 4点のサンプルを取得
         let p0 = idx_i.wrapping_sub(1) & mask;
@@ -47,17 +101,26 @@ impl DelayLine {
         let p2 = idx_i.wrapping_add(1) & mask;
         let p3 = idx_i.wrapping_add(2) & mask;
 
-        let s0 = self.data[p0];
-        let s1 = self.data[p1];
-        let s2 = self.data[p2];
-        let s3 = self.data[p3];
+        ([self.data[p0], self.data[p1], self.data[p2], self.data[p3]], frac)
+    }
+}
+
+/**
+ * 4点エルミート補間の公式（スカラーでも SIMD のレーンでも同じ式で計算する）
+ * - constant 定数を T に変換する関数
+ */
+#[inline]
+fn hermite<T>(points: [T; 4], frac: T, constant: impl Fn(f32) -> T) -> T
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Mul<Output = T>,
+{
+    let [s0, s1, s2, s3] = points;
+    let half = constant(0.5);
 
-        // 4点エルミート補間の公式
-        let c0 = s1;
-        let c1 = 0.5 * (s2 - s0);
-        let c2 = s0 - 2.5 * s1 + 2.0 * s2 - 0.5 * s3;
-        let c3 = 0.5 * (s3 - s0) + 1.5 * (s1 - s2);
+    let c0 = s1;
+    let c1 = half * (s2 - s0);
+    let c2 = s0 - constant(2.5) * s1 + constant(2.0) * s2 - half * s3;
+    let c3 = half * (s3 - s0) + constant(1.5) * (s1 - s2);
 
-        ((c3 * frac + c2) * frac + c1) * frac + c0
-    }
+    ((c3 * frac + c2) * frac + c1) * frac + c0
 }
\ No newline at end of file
@@ -0,0 +1,127 @@
+#[cfg(feature = "plugin")]
+use nih_plug::prelude::Enum;
+
+// PitchLinear の面積を級数で求めるときの項数（-60 st でも十分収束する）
+const PITCH_SERIES_TERMS: usize = 40;
+
+// カーブの種類
+#[derive(PartialEq, Clone, Copy, Debug)]
+#[cfg_attr(feature = "plugin", derive(Enum))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(rename_all = "snake_case"))]
+pub enum TapeCurve {
+    Linear,      // 直線的
+    Smooth,      // 滑らか
+    SlowStart,   // 慣性あり
+    QuickCut,    // 急に落ち始め、最後にゆっくり
+    PitchLinear, // 半音単位で一定の速さで下がり、下限で止まる
+}
+
+impl TapeCurve {
+    // すべてのカーブ
+    pub const ALL: [TapeCurve; 5] = [
+        TapeCurve::Linear,
+        TapeCurve::Smooth,
+        TapeCurve::SlowStart,
+        TapeCurve::QuickCut,
+        TapeCurve::PitchLinear,
+    ];
+}
+
+/**
+ * カーブの種類に応じて進行度を速度に変換
+ * - pitch_floor_st PitchLinear で止まる直前の音程（半音）
+ */
+pub fn apply_curve(curve: TapeCurve, t: f64, pitch_floor_st: f64) -> f64 {
+    match curve {
+        TapeCurve::Linear => t,
+        TapeCurve::Smooth => t * t * (3.0 - 2.0 * t),
+        TapeCurve::SlowStart => 1.0 - (1.0 - t).powi(2),
+        TapeCurve::QuickCut => t.powi(3),
+        TapeCurve::PitchLinear => {
+            // 音程 (半音) を進行度に比例して下げ、phase が 0 になったところで止める
+            if t <= 0.0 {
+                0.0
+            } else {
+                2.0f64.powf(pitch_floor_st.min(0.0) * (1.0 - t) / 12.0)
+            }
+        }
+    }
+}
+
+/**
+ * カーブの面積 ∫ c(s^exponent) ds を from -> to の範囲で求める
+ * ヘッドの移動量をサンプルごとの速度の積算ではなく面積から求めることで、
+ * サンプルレートや浮動小数点の誤差によらず停止までに進む距離を一定にする
+ */
+pub fn curve_area(curve: TapeCurve, from: f64, to: f64, exponent: f64, pitch_floor_st: f64) -> f64 {
+    // s^p の原始関数
+    let power = |p: f64| move |t: f64| t.max(0.0).powf(p + 1.0) / (p + 1.0);
+    let e = exponent;
+
+    let antiderivative = |t: f64| -> f64 {
+        match curve {
+            TapeCurve::Linear => power(e)(t),
+            TapeCurve::Smooth => 3.0 * power(2.0 * e)(t) - 2.0 * power(3.0 * e)(t),
+            TapeCurve::SlowStart => 2.0 * power(e)(t) - power(2.0 * e)(t),
+            TapeCurve::QuickCut => power(3.0 * e)(t),
+            TapeCurve::PitchLinear => {
+                // exp(k(1 - s)) の原始関数。phase 0 の1点だけ速度 0 になるが面積には影響しない
+                let k = pitch_floor_st.min(0.0) * std::f64::consts::LN_2 / 12.0;
+                if k == 0.0 {
+                    t
+                } else if e == 1.0 {
+                    -(k * (1.0 - t)).exp() / k
+                } else {
+                    // スプレッドで指数がずれたときは exp(-k s^e) をべき級数に展開して項ごとに積分する
+                    let t = t.max(0.0);
+                    let u = t.powf(e);
+                    let mut coefficient = 1.0; // (-k)^n / n!
+                    let mut u_n = 1.0;         // u^n
+                    let mut sum = 0.0;
+                    for n in 0..PITCH_SERIES_TERMS {
+                        sum += coefficient * u_n / (n as f64 * e + 1.0);
+                        coefficient *= -k / (n + 1) as f64;
+                        u_n *= u;
+                    }
+                    k.exp() * t * sum
+                }
+            }
+        }
+    };
+
+    antiderivative(to) - antiderivative(from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pitch_linear_ramp_falls_evenly_in_semitones() {
+        let floor = -36.0;
+        let semitones = |t: f64| 12.0 * apply_curve(TapeCurve::PitchLinear, t, floor).log2();
+
+        assert_eq!(apply_curve(TapeCurve::PitchLinear, 1.0, floor), 1.0);
+        assert!((semitones(0.5) - floor * 0.5).abs() < 1.0e-9);
+        assert!((semitones(0.25) - semitones(0.5) - (semitones(0.5) - semitones(0.75))).abs() < 1.0e-9);
+        assert!((semitones(1.0e-9) - floor).abs() < 1.0e-6);
+        assert_eq!(apply_curve(TapeCurve::PitchLinear, 0.0, floor), 0.0);
+    }
+
+    #[test]
+    fn curve_area_matches_numerical_integration() {
+        for curve in TapeCurve::ALL {
+            for exponent in [1.0, 0.7, 1.4] {
+                let n = 100_000;
+                let numeric: f64 = (0..n)
+                    .map(|i| {
+                        let t = (i as f64 + 0.5) / n as f64;
+                        apply_curve(curve, t.powf(exponent), -36.0) / n as f64
+                    })
+                    .sum();
+                let exact = curve_area(curve, 0.0, 1.0, exponent, -36.0);
+                assert!((numeric - exact).abs() < 1.0e-6, "{curve:?} ^{exponent}: {numeric} vs {exact}");
+            }
+        }
+    }
+}
\ No newline at end of file
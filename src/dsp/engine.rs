@@ -1,37 +1,398 @@
 use super::buffer::DelayLine;
 use super::filter::OnePoleLowpass;
-use crate::params::{TapeCurve, SyncBeat};
+use super::hold::GrainHold;
+use super::limiter::SoftClipper;
+use super::lofi::LofiDegrader;
+use super::noise::NoiseGenerator;
+use super::sample::{Sample, SampleLanes, LANES};
+use super::saturation::TapeSaturator;
+use super::width;
+use super::wow::WowFlutter;
+use super::curve::{apply_curve, curve_area, TapeCurve};
+use super::sync::{HoldDivision, SyncBeat};
 
-pub struct TapeStopEngine {
-    buffers: Vec<DelayLine>,      // チャンネルごとの遅延バッファ
-    filters: Vec<OnePoleLowpass>, // チャンネルごとのフィルタ
-    sample_rate: f32,             // サンプルレート
+/**
+ * 1サンプルごとにエンジンへ渡すパラメータ
+ */
+#[derive(Clone, Copy)]
+pub struct EngineParams {
+    pub trigger: bool,          // テープストップトリガー
+    pub stop_time_sec: f32,     // 停止時間（秒）
+    pub start_time_sec: f32,    // 再生開始時間（秒）
+    pub curve: TapeCurve,       // カーブの種類
+    pub use_sync: bool,         // BPM同期を使うかどうか
+    pub sync_beat: SyncBeat,    // BPM同期時の拍数指定
+    pub bpm: f64,               // ホストからのBPM情報
+    pub enable_filter: bool,    // ローパスフィルターを有効にするかどうか
+    pub mix: f32,               // ドライ/ウェット (0.0 = ドライのみ, 1.0 = ウェットのみ)
+    pub output_gain: f32,       // 出力ゲイン（リニア）
+    pub enable_limiter: bool,   // 出力段のソフトクリップを有効にするかどうか
+    pub enable_wow: bool,       // ワウ・フラッターを有効にするかどうか
+    pub wow_rate: f32,          // ワウの周波数 (Hz)
+    pub wow_depth: f32,         // 揺れの深さ (0.0 -> 1.0)
+    pub wow_instability: f32,   // フラッターの乱雑さ (0.0 -> 1.0)
+    pub enable_saturation: bool, // テープ飽和を有効にするかどうか
+    pub saturation_drive: f32,  // 歪みの強さ (0.0 -> 1.0)
+    pub saturation_bias: f32,   // 非対称さ (-1.0 -> 1.0)
+    pub saturation_mix: f32,    // 飽和段のミックス (0.0 -> 1.0)
+    pub saturation_link: bool,  // 速度が落ちるほど drive を上げるかどうか
+    pub enable_decimate: bool,  // サンプルレート低下を有効にするかどうか
+    pub decimate_amount: f32,   // 停止時の間引きの強さ (0.0 -> 1.0)
+    pub enable_bitcrush: bool,  // ビット深度低下を有効にするかどうか
+    pub bitcrush_amount: f32,   // 停止時のビット削減の強さ (0.0 -> 1.0)
+    pub lofi_anti_alias: bool,  // 間引き前にエイリアス除去をするかどうか
+    pub stereo_spread: f32,     // 左右で停止タイミングをずらす量 (0.0 -> 1.0)
+    pub enable_width: bool,     // 減速に合わせてステレオ幅を狭めるかどうか
+    pub width_at_stop: f32,     // 停止時のステレオ幅 (0.0 = モノラル, 1.0 = そのまま)
+    pub width_response: f32,    // 速度に対する幅の変化カーブ（指数。1.0 で直線）
+    pub enable_noise: bool,     // テープノイズを足すかどうか
+    pub hiss_level: f32,        // テープヒスの量 (0.0 -> 1.0)
+    pub crackle_level: f32,     // クラックルの量 (0.0 -> 1.0)
+    pub motor_level: f32,       // モーター音の量 (0.0 -> 1.0)
+    pub enable_hold: bool,      // 停止位置でグレインをループするかどうか
+    pub hold_division: HoldDivision, // グレインの長さ（拍）
+    pub hold_crossfade: f32,    // ループのつなぎ目のクロスフェード（グレイン長に対する割合 0.0 -> 0.5）
+    pub hold_repeats: u32,      // ループ回数 (0 = 無限)
+    pub pitch_floor_st: f32,    // PitchLinear で止まる直前の音程（半音、負の値）
+    pub land_on_beat: bool,     // BPM同期時、同期した長さぶんのテープをちょうど使い切って止めるかどうか
+}
 
-    write_pos: usize, // 書き込み位置
-    read_pos: f64,    // 読み込み位置
+impl Default for EngineParams {
+    fn default() -> Self {
+        Self {
+            trigger: false,
+            stop_time_sec: 0.5,
+            start_time_sec: 0.5,
+            curve: TapeCurve::Linear,
+            use_sync: false,
+            sync_beat: SyncBeat::Quarter,
+            bpm: 120.0,
+            enable_filter: true,
+            mix: 1.0,
+            output_gain: 1.0,
+            enable_limiter: false,
+            enable_wow: false,
+            wow_rate: 0.8,
+            wow_depth: 0.3,
+            wow_instability: 0.5,
+            enable_saturation: false,
+            saturation_drive: 0.3,
+            saturation_bias: 0.2,
+            saturation_mix: 1.0,
+            saturation_link: true,
+            enable_decimate: false,
+            decimate_amount: 0.5,
+            enable_bitcrush: false,
+            bitcrush_amount: 0.5,
+            lofi_anti_alias: true,
+            stereo_spread: 0.0,
+            enable_width: false,
+            width_at_stop: 0.0,
+            width_response: 1.0,
+            enable_noise: false,
+            hiss_level: 0.3,
+            crackle_level: 0.2,
+            motor_level: 0.2,
+            enable_hold: false,
+            hold_division: HoldDivision::Sixteenth,
+            hold_crossfade: 0.1,
+            hold_repeats: 0,
+            pitch_floor_st: -36.0,
+            land_on_beat: false,
+        }
+    }
+}
+
+// 復帰時のクロスフェード時間（秒）
+const CROSSFADE_SEC: f32 = 0.1;
+// ワウ・フラッターの乱数シード
+const WOW_SEED: u32 = 0x4B32_5354;
+// ノイズの乱数シード（チャンネル番号を足して使う）
+const NOISE_SEED: u32 = 0x4E4F_4953;
+// スプレッド 1.0 のときの停止/再始動時間のずれ（±25%）
+const SPREAD_TIME: f64 = 0.25;
+// スプレッド 1.0 のときのカーブのずれ（進行度の指数を 2^±0.5 倍）
+const SPREAD_CURVE: f64 = 0.5;
+
+// 停止/再始動時間の上限（秒）
+pub const MAX_RAMP_SEC: f32 = 2.0;
+// PitchLinear の下限音程の範囲の下端（半音）
+pub const MIN_PITCH_FLOOR_ST: f32 = -60.0;
+// BPM同期でバッファが足りることを保証する最低テンポ
+pub const MIN_SYNC_BPM: f64 = 60.0;
+// バッファで保持する遅れの上限（秒）
+// Land on Beat や PitchLinear の深い下限を遅いテンポで使うと遅れが数十秒になるので、そこまでは確保せず停止時間を切り詰める
+pub const MAX_LAG_SEC: f64 = 8.0;
+// 補間やクロスフェードのためにバッファに持たせる余裕（秒）
+const CAPACITY_MARGIN_SEC: f64 = 0.1;
+// バッファに収まらないときに切り詰める停止時間の下限（秒）
+const MIN_CLAMPED_STOP_SEC: f64 = 0.01;
+
+/**
+ * テープの状態
+ */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TapeState {
+    Playing,     // 通常再生（入力をそのまま通している）
+    Stopping,    // 減速中
+    Stopped,     // 完全に停止している
+    Starting,    // 再始動中
+    Crossfading, // リアルタイム音へクロスフェード中
+}
+
+/**
+ * ランプの間に書き込み位置から遅れる秒数
+ * ランプの長さのうち、カーブの面積ぶんだけヘッドが進むので、残りが遅れになる
+ */
+fn ramp_lag(seconds: f64, area: f64) -> f64 {
+    seconds * (1.0 - area).max(0.0)
+}
+
+/**
+ * パラメータの範囲で起こりうる最大の遅れ（秒）を求める
+ * 停止ランプ、再始動ランプ（またはホールドのグレイン2つぶん）の遅れの合計を、
+ * カーブ・スプレッド・同期の長さ・Land on Beat のすべての組み合わせで一番大きくなるように見積もる
+ * 完全に止まっている間の遅れは止めている時間しだいなので含まない
+ * - min_bpm 同期で対応する最低テンポ
+ */
+fn worst_case_lag_seconds(min_bpm: f64) -> f64 {
+    let exponents = [2.0f64.powf(-SPREAD_CURVE), 1.0, 2.0f64.powf(SPREAD_CURVE)];
+    let max_scale = 1.0 + SPREAD_TIME;
+    let max_ramp = MAX_RAMP_SEC as f64 * max_scale;
+    let synced = 60.0 / min_bpm * SyncBeat::TwoBars.beats();
+
+    let mut stop_lag: f64 = 0.0;
+    let mut start_lag: f64 = 0.0;
+    for curve in TapeCurve::ALL {
+        for exponent in exponents {
+            let area = curve_area(curve, 0.0, 1.0, exponent, MIN_PITCH_FLOOR_ST as f64);
+            let stop = max_ramp.max(synced * max_scale).max(synced / area.max(1.0e-6));
+            stop_lag = stop_lag.max(ramp_lag(stop, area));
+            start_lag = start_lag.max(ramp_lag(max_ramp, area));
+        }
+    }
+
+    // ホールドは停止位置から 1/4 拍のグレインとその手前を読む
+    let hold_lag = 2.0 * 60.0 / min_bpm;
+    stop_lag + start_lag.max(hold_lag) + CAPACITY_MARGIN_SEC
+}
+
+/**
+ * ステレオスプレッド用のチャンネル位置 (-1.0 = 左, 1.0 = 右)
+ * 幅と同じく先頭の L/R だけを広げ、3チャンネル目以降（C、LFE、リアなど）は中央のままにする
+ */
+fn spread_position(channel: usize, channels: usize) -> f64 {
+    match channel {
+        0 if channels >= 2 => -1.0,
+        1 => 1.0,
+        _ => 0.0,
+    }
+}
 
+/**
+ * テープ速度からフィルタのカットオフを求める
+ * 速度が低いほどこもらせる
+ */
+#[inline]
+fn filter_cutoff(speed: f32) -> f32 {
+    let min_cut: f32 = 200.0;
+    let max_cut: f32 = 20000.0;
+    min_cut * (max_cut / min_cut).powf(speed)
+}
+
+/**
+ * チャンネルごとの再生ヘッド
+ */
+#[derive(Clone, Copy)]
+struct TapeHead {
+    read_pos: f64,       // 読み込み位置
     phase: f64,          // 1.0 -> 0.0の進行度
     current_speed: f64,  // phaseとcurveから計算された実際の速度
     crossfade_gain: f32, // テープ音とリアルタイム音のクロスフェードゲイン
+    start_step: f64,     // 直前のサンプルで使った再始動の変化量
+    distance: f64,       // このサンプルで進む距離
 }
 
-impl TapeStopEngine {
+impl TapeHead {
+    fn new(sample_rate: f32) -> Self {
+        Self {
+            read_pos: 0.0,
+            phase: 1.0,
+            current_speed: 1.0,
+            crossfade_gain: 1.0,
+            start_step: 1.0 / (0.5 * sample_rate) as f64,
+            distance: 1.0,
+        }
+    }
+
+    /**
+     * 進行度とクロスフェードを1サンプル進める
+     */
+    fn advance(&mut self, trigger: bool, stop_step: f64, start_step: f64, xfade_step: f32, write_pos: usize) {
+        self.start_step = start_step;
+
+        if trigger {
+            // Phaseを 1.0 -> 0.0 へ減らす
+            self.phase -= stop_step;
+            if self.phase < 0.0 { self.phase = 0.0; }
+            self.crossfade_gain = 0.0;
+        } else {
+            // Phaseを 0.0 -> 1.0 へ増やす
+            if self.phase < 1.0 {
+                self.phase += start_step;
+                if self.phase > 1.0 { self.phase = 1.0; }
+                self.crossfade_gain = 0.0;
+            } else {
+                // Phaseが1.0に戻ったらクロスフェードで復帰
+                if self.crossfade_gain < 1.0 {
+                    self.crossfade_gain += xfade_step;
+                    if self.crossfade_gain >= 1.0 {
+                        self.crossfade_gain = 1.0;
+                        self.read_pos = write_pos as f64; // 同期
+                    }
+                }
+            }
+        }
+    }
+
+    /**
+     * トリガーが離された状態で、通常再生に戻るまでに必要なサンプル数
+     */
+    fn tail_samples(&self, xfade_step: f32) -> u32 {
+        if self.phase >= 1.0 && self.crossfade_gain >= 1.0 {
+            return 0;
+        }
+
+        // 進行度の積算誤差で1サンプルずれることがあるので、各段階に1サンプルの余裕を持たせる
+        let ramp = if self.phase < 1.0 {
+            ((1.0 - self.phase) / self.start_step).ceil() as u32 + 1
+        } else {
+            0
+        };
+        let xfade = ((1.0 - self.crossfade_gain) / xfade_step).ceil() as u32 + 1;
+        ramp + xfade
+    }
+}
+
+/**
+ * テープストップエンジン本体
+ * 音声が通る経路（バッファ、補間、フィルタ、クロスフェード、ミックス）はサンプル型 S で計算する
+ * 飽和・ローファイ・ノイズの色付けは f32 で処理して S に戻す
+ */
+pub struct TapeStopEngine<S: Sample = f32> {
+    buffers: Vec<DelayLine<S>>,      // チャンネルごとの遅延バッファ
+    filters: Vec<OnePoleLowpass<S>>, // チャンネルごとのフィルタ
+    saturators: Vec<TapeSaturator>, // チャンネルごとのテープ飽和
+    degraders: Vec<LofiDegrader>, // チャンネルごとのローファイ処理
+    heads: Vec<TapeHead>,         // チャンネルごとの再生ヘッド
+    noises: Vec<NoiseGenerator>,  // チャンネルごとのノイズ
+    holds: Vec<GrainHold>,        // チャンネルごとのグレインループ
+    limiter: SoftClipper,         // 出力段のソフトクリッパー
+    wow: WowFlutter,              // ヘッド速度の揺れ（全ヘッドで共有）
+    sample_rate: f32,             // サンプルレート
+    max_seconds: f32,             // バッファに保持できる秒数
+
+    write_pos: usize, // 書き込み位置
+    triggered: bool,  // 直前のサンプルでトリガーされていたかどうか
+    simd: bool,       // 条件がそろったときに SIMD でまとめて処理するかどうか
+    clamped: bool,    // 直前のサンプルで停止時間をバッファに合わせて切り詰めたかどうか
+    synced_stop_sec: f64, // トリガーした瞬間のテンポで決めた同期の停止時間
+}
+
+impl<S: Sample> TapeStopEngine<S> {
     pub fn new(sample_rate: f32, max_seconds: f32, channels: usize) -> Self {
         let buffers = (0..channels).map(|_| DelayLine::new(max_seconds, sample_rate)).collect();
         let filters = (0..channels).map(|_| OnePoleLowpass::new()).collect();
+        let saturators = (0..channels).map(|_| TapeSaturator::new()).collect();
+        let degraders = (0..channels).map(|_| LofiDegrader::new()).collect();
+        let heads = (0..channels).map(|_| TapeHead::new(sample_rate)).collect();
+        let noises = (0..channels).map(|ch| NoiseGenerator::new(NOISE_SEED.wrapping_add(ch as u32))).collect();
+        let holds = (0..channels).map(|_| GrainHold::new()).collect();
 
         Self {
             buffers,
             filters,
+            saturators,
+            degraders,
+            heads,
+            noises,
+            holds,
+            limiter: SoftClipper::new(),
+            wow: WowFlutter::new(WOW_SEED),
             sample_rate,
+            max_seconds,
             write_pos: 0,
-            read_pos: 0.0,
-            phase: 1.0,
-            current_speed: 1.0,
-            crossfade_gain: 1.0,
+            triggered: false,
+            simd: true,
+            clamped: false,
+            synced_stop_sec: 0.0,
         }
     }
 
+    /**
+     * パラメータの範囲で起こりうる最大の遅れを保持できるバッファの長さ（秒）
+     * ただし MAX_LAG_SEC を超える分は確保せず、その組み合わせでは停止時間を切り詰める（is_clamped で分かる）
+     * - min_bpm 同期で対応する最低テンポ（これより遅いと停止時間が切り詰められる）
+     */
+    pub fn required_seconds(min_bpm: f64) -> f32 {
+        worst_case_lag_seconds(min_bpm).min(MAX_LAG_SEC + CAPACITY_MARGIN_SEC) as f32
+    }
+
+    /**
+     * サンプルレートとバッファの長さを変更する（リアルタイムスレッドから呼ばないこと）
+     * バッファは足りないときだけ確保し直し、再生状態はリセットされる
+     */
+    pub fn prepare(&mut self, sample_rate: f32, max_seconds: f32) {
+        self.sample_rate = sample_rate;
+        self.max_seconds = max_seconds;
+        for buffer in &mut self.buffers {
+            buffer.resize(max_seconds, sample_rate);
+        }
+        self.reset();
+    }
+
+    /**
+     * サンプルレートだけを変更する（prepare と同じく非リアルタイム）
+     */
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.prepare(sample_rate, self.max_seconds);
+    }
+
+    /**
+     * バッファの長さだけを変更する（prepare と同じく非リアルタイム）
+     */
+    pub fn set_max_seconds(&mut self, max_seconds: f32) {
+        self.prepare(self.sample_rate, max_seconds);
+    }
+
+    /**
+     * 停止時間をバッファに収まるよう切り詰めているかどうか
+     */
+    pub fn is_clamped(&self) -> bool {
+        self.clamped
+    }
+
+    /**
+     * 現在のサンプルレートを取得
+     */
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    /**
+     * 処理するチャンネル数を取得
+     */
+    pub fn channels(&self) -> usize {
+        self.heads.len()
+    }
+
+    /**
+     * SIMD 処理の有効/無効を切り替える（無効にすると常にスカラーで処理する）
+     */
+    pub fn set_simd(&mut self, enabled: bool) {
+        self.simd = enabled;
+    }
+
     /**
      * エンジンの状態をリセット
      */
@@ -42,128 +403,875 @@ impl TapeStopEngine {
         for filter in &mut self.filters {
             filter.reset();
         }
+        for saturator in &mut self.saturators {
+            saturator.reset();
+        }
+        for degrader in &mut self.degraders {
+            degrader.reset();
+        }
+        for head in &mut self.heads {
+            *head = TapeHead::new(self.sample_rate);
+        }
+        for noise in &mut self.noises {
+            noise.reset();
+        }
+        for hold in &mut self.holds {
+            hold.release();
+        }
+        self.wow.reset();
         self.write_pos = 0;
-        self.read_pos = 0.0;
-        self.phase = 1.0;
-        self.current_speed = 1.0;
-        self.crossfade_gain = 1.0;
+        self.triggered = false;
+        self.clamped = false;
+        self.synced_stop_sec = 0.0;
+    }
+
+    /**
+     * 現在のテープの状態を取得
+     * ヘッドごとに進み方が違う場合は、一番遅れているヘッドに合わせる
+     */
+    pub fn state(&self) -> TapeState {
+        if self.triggered {
+            if self.heads.iter().any(|h| h.phase > 0.0) { TapeState::Stopping } else { TapeState::Stopped }
+        } else if self.heads.iter().any(|h| h.phase < 1.0) {
+            TapeState::Starting
+        } else if self.heads.iter().any(|h| h.crossfade_gain < 1.0) {
+            TapeState::Crossfading
+        } else {
+            TapeState::Playing
+        }
+    }
+
+    /**
+     * トリガーが離された状態で、通常再生に戻るまでに必要なサンプル数を取得
+     * トリガー中は再始動のタイミングが決まらないため、呼び出し側で別扱いにすること
+     */
+    pub fn tail_samples(&self) -> u32 {
+        let xfade_step = 1.0 / (CROSSFADE_SEC * self.sample_rate);
+        self.heads.iter().map(|h| h.tail_samples(xfade_step)).max().unwrap_or(0)
     }
 
     /**
      * テープストップエフェクトを処理
      * - input 入力バッファ
      * - output 出力バッファ
-     * -  trigger テープストップトリガー
-     * - stop_time_sec 停止時間（秒）
-     * - start_time_sec 再生開始時間（秒）
-     * - curve_type カーブの種類
-     * - use_sync BPM同期を使うかどうか
-     * - sync_beat BPM同期時の拍数指定
-     * - bpm ホストからのBPM情報
-     * - enable_filter ローパスフィルターを有効にするかどう
-     */
-    pub fn process(
-        &mut self,
-        input: &[f32],
-        output: &mut [f32],
-        trigger: bool,
-        stop_time_sec: f32,
-        start_time_sec: f32,
-        curve_type: TapeCurve,
-        use_sync: bool,
-        sync_beat: SyncBeat,
-        bpm: f64,
-        enable_filter: bool,
-    ) {
+     * - params 1サンプル分のパラメータ
+     */
+    pub fn process(&mut self, input: &[S], output: &mut [S], params: &EngineParams) {
         // 時間の決定
-        let actual_stop_time = if use_sync {
-            // BPM同期
-            let current_bpm = bpm; // BPMが取れないときは120
-            let beats = match sync_beat {
-                SyncBeat::Eight => 0.5,
-                SyncBeat::Quarter => 1.0,
-                SyncBeat::Half => 2.0,
-                SyncBeat::OneBar => 4.0,
-                SyncBeat::TwoBars => 8.0,
-            };
-            // 時間 = (60 / BPM) * 拍数
-            (60.0 / current_bpm as f32) * beats
+        // BPM同期: 時間 = (60 / BPM) * 拍数（BPMが取れないときは120）
+        let synced_stop_sec = (60.0 / params.bpm) * params.sync_beat.beats();
+        if params.trigger && !self.triggered {
+            // 同期の長さはトリガーした瞬間のテンポで固定し、ランプ中のテンポ変化では刻み幅を変えない
+            self.synced_stop_sec = synced_stop_sec;
+        }
+        let actual_stop_time = if params.use_sync {
+            if params.trigger { self.synced_stop_sec } else { synced_stop_sec }
         } else {
-            stop_time_sec
+            params.stop_time_sec as f64
         };
 
-        // 変化量の計算
-        let stop_step = 1.0 / (actual_stop_time * self.sample_rate) as f64;
-        let start_step = 1.0 / (start_time_sec * self.sample_rate) as f64;
-        let xfade_step = 1.0 / (0.1 * self.sample_rate);
+        let xfade_step = 1.0 / (CROSSFADE_SEC * self.sample_rate);
+        self.triggered = params.trigger;
 
-        // 進行度の更新
-        if trigger {
-            // Phaseを 1.0 -> 0.0 へ減らす
-            self.phase -= stop_step;
-            if self.phase < 0.0 { self.phase = 0.0; }
-            self.crossfade_gain = 0.0;
-        } else {
-            // Phaseを 0.0 -> 1.0 へ増やす
-            if self.phase < 1.0 {
-                self.phase += start_step;
-                if self.phase > 1.0 { self.phase = 1.0; }
-                self.crossfade_gain = 0.0;
+        // ホールドのグレイン長（テンポ同期）
+        let grain_len = 60.0 / params.bpm * params.hold_division.beats() * self.sample_rate as f64;
+        let grain_xfade = grain_len * params.hold_crossfade.clamp(0.0, 0.5) as f64;
+
+        // 揺れは1本のテープとして全ヘッドで共有する
+        let any_ramp = self.heads.iter().any(|h| h.phase < 1.0);
+        if params.enable_wow && any_ramp {
+            self.wow.advance(params.wow_rate, params.wow_instability, self.sample_rate);
+        }
+
+        let channels = self.heads.len();
+        let spread = params.stereo_spread.clamp(0.0, 1.0) as f64;
+
+        // 音声処理
+        let channels_used = input.len().min(output.len()).min(self.buffers.len());
+
+        // ヘッドの進行と書き込み
+        let mut clamped = false;
+        for (ch, &in_sample) in input.iter().enumerate().take(channels_used) {
+            // チャンネルごとに停止/再始動時間とカーブをずらす
+            let offset = spread * spread_position(ch, channels);
+            let time_scale = 1.0 + SPREAD_TIME * offset;
+            let curve_exponent = 2.0f64.powf(SPREAD_CURVE * offset);
+
+            let pitch_floor_st = params.pitch_floor_st as f64;
+            let start_time = params.start_time_sec as f64 * time_scale;
+            let mut stop_time = if params.use_sync && params.land_on_beat {
+                // 停止までに進む距離 = 停止時間 × カーブの面積 なので、
+                // 同期した長さぶんのテープをちょうど使い切るよう停止時間を伸ばす
+                let area = curve_area(params.curve, 0.0, 1.0, curve_exponent, pitch_floor_st);
+                actual_stop_time / area.max(1.0e-6)
             } else {
-                // Phaseが1.0に戻ったらクロスフェードで復帰
-                if self.crossfade_gain < 1.0 {
-                    self.crossfade_gain += xfade_step;
-                    if self.crossfade_gain >= 1.0 {
-                        self.crossfade_gain = 1.0;
-                        self.read_pos = self.write_pos as f64; // 同期
-                    }
+                actual_stop_time * time_scale
+            };
+
+            // 遅れがバッファに収まらない長さ（遅いテンポでの同期など）は切り詰める
+            // 補間のための余裕は残しておく
+            let capacity = self.max_seconds as f64 - CAPACITY_MARGIN_SEC;
+            if stop_time + start_time > capacity {
+                let area = curve_area(params.curve, 0.0, 1.0, curve_exponent, pitch_floor_st);
+                let lag_limit = capacity - ramp_lag(start_time, area);
+                let max_stop = (lag_limit / (1.0 - area).max(1.0e-6)).max(MIN_CLAMPED_STOP_SEC);
+                if stop_time > max_stop {
+                    stop_time = max_stop;
+                    clamped = true;
                 }
             }
+
+            // 変化量の計算
+            let stop_step = 1.0 / (stop_time * self.sample_rate as f64);
+            let start_step = 1.0 / (start_time * self.sample_rate as f64);
+
+            // 進行度の更新
+            let head = &mut self.heads[ch];
+            let prev_phase = head.phase;
+            head.advance(params.trigger, stop_step, start_step, xfade_step, self.write_pos);
+
+            // Curve適用
+            let t = head.phase.powf(curve_exponent);
+            head.current_speed = apply_curve(params.curve, t, pitch_floor_st);
+
+            // このサンプルで進む距離（ランプ中はカーブの面積から求める）
+            let mut distance = if head.phase != prev_phase {
+                let step = if params.trigger { stop_step } else { start_step };
+                let (from, to) = if prev_phase < head.phase { (prev_phase, head.phase) } else { (head.phase, prev_phase) };
+                curve_area(params.curve, from, to, curve_exponent, pitch_floor_st) / step
+            } else {
+                head.current_speed
+            };
+
+            // ランプ中だけヘッド速度を揺らす（通常再生中は書き込み位置に追従させる）
+            if params.enable_wow && head.phase < 1.0 && head.current_speed > 0.0 {
+                let head_speed = self.wow.apply(head.current_speed, params.wow_depth, params.wow_instability);
+                distance *= head_speed / head.current_speed;
+            }
+            head.distance = distance;
+
+            // 書き込み
+            self.buffers[ch].write(self.write_pos, in_sample);
+        }
+
+        self.clamped = clamped;
+
+        // 全ヘッドが同じ位置にいて、チャンネルごとに状態を持つ色付けも無効なら SIMD でまとめて読む
+        let colored = params.enable_hold
+            || params.enable_saturation
+            || params.enable_decimate
+            || params.enable_bitcrush
+            || params.enable_noise;
+        if self.simd && !colored && self.heads_locked(channels_used) {
+            self.process_lanes(&input[..channels_used], &mut output[..channels_used], params.enable_filter);
+        } else {
+            for (ch, (&in_sample, out_sample)) in input.iter().zip(output.iter_mut()).take(channels_used).enumerate() {
+                *out_sample = self.process_channel(ch, in_sample, params, grain_len, grain_xfade);
+            }
         }
 
-        // Curve適用
-        let t = self.phase;
-        self.current_speed = match curve_type {
-            TapeCurve::Linear => t,
-            TapeCurve::Smooth => t * t * (3.0 - 2.0 * t),
-            TapeCurve::SlowStart => 1.0 - (1.0 - t).powi(2),
-            TapeCurve::QuickCut => t.powi(3),
+        // ヘッド進行
+        for head in self.heads.iter_mut().take(channels_used) {
+            head.read_pos += head.distance;
+        }
+
+        // ステレオ幅（先頭の L/R ペアだけに適用し、モノラルでは何もしない）
+        if params.enable_width && self.heads.len() >= 2 && output.len() >= 2 {
+            let speed = (0.5 * (self.heads[0].current_speed + self.heads[1].current_speed)).clamp(0.0, 1.0) as f32;
+            let response = speed.powf(params.width_response.max(0.01));
+            let width = params.width_at_stop + (1.0 - params.width_at_stop) * response;
+            (output[0], output[1]) = width::mid_side(output[0], output[1], width);
+        }
+
+        for (&in_sample, out_sample) in input.iter().zip(output.iter_mut()).take(self.buffers.len()) {
+            // ドライ/ウェットと出力ゲイン
+            let mix = S::from_f32(params.mix);
+            let mut out = (in_sample * (S::ONE - mix) + *out_sample * mix) * S::from_f32(params.output_gain);
+
+            // 補間のオーバーシュート対策
+            if params.enable_limiter {
+                out = self.limiter.process(out);
+            }
+
+            *out_sample = out;
+        }
+
+        self.write_pos = self.write_pos.wrapping_add(1);
+    }
+
+    /**
+     * 全ヘッドが同じ位置・速度・クロスフェードにいるかどうか
+     */
+    fn heads_locked(&self, channels: usize) -> bool {
+        let heads = &self.heads[..channels];
+        match heads.first() {
+            Some(first) => heads.iter().all(|h| {
+                h.read_pos == first.read_pos
+                    && h.current_speed == first.current_speed
+                    && h.crossfade_gain == first.crossfade_gain
+            }),
+            None => false,
+        }
+    }
+
+    /**
+     * LANES チャンネルずつまとめて読み込み・フィルタ・クロスフェードを行う（SIMD）
+     * ヘッドがそろっているときだけ呼ぶこと
+     */
+    fn process_lanes(&mut self, input: &[S], output: &mut [S], enable_filter: bool) {
+        let head = self.heads[0];
+        let crossfade_gain = S::from_f32(head.crossfade_gain);
+        let dry_gain = S::Lanes::splat(crossfade_gain);
+        let tape_gain = S::Lanes::splat(S::ONE - crossfade_gain);
+        let alpha = OnePoleLowpass::<S>::coefficient(filter_cutoff(head.current_speed as f32), self.sample_rate);
+
+        for start in (0..input.len()).step_by(LANES) {
+            let end = (start + LANES).min(input.len());
+
+            let mut tape_sound = DelayLine::read_lanes(&self.buffers[start..end], head.read_pos);
+            if enable_filter {
+                tape_sound = OnePoleLowpass::process_lanes(&mut self.filters[start..end], alpha, tape_sound);
+            }
+
+            let mut dry = [S::ZERO; LANES];
+            dry[..end - start].copy_from_slice(&input[start..end]);
+            let out = tape_sound * tape_gain + S::Lanes::from_array(dry) * dry_gain;
+            output[start..end].copy_from_slice(&out.to_array()[..end - start]);
+        }
+
+        // スカラー版と同じく、ホールドが無効な間はループを解除しておく
+        for hold in &mut self.holds[..input.len()] {
+            hold.release();
+        }
+    }
+
+    /**
+     * 1チャンネル分の読み込みから色付け、クロスフェードまでを行う（スカラー）
+     */
+    fn process_channel(&mut self, ch: usize, in_sample: S, params: &EngineParams, grain_len: f64, grain_xfade: f64) -> S {
+        let head = self.heads[ch];
+        let speed = head.current_speed as f32;
+        let slowdown = (1.0 - speed).clamp(0.0, 1.0);
+
+        // 読み込み（完全に止まったらグレインをループさせる）
+        let held = if params.enable_hold && params.trigger && head.phase <= 0.0 {
+            self.holds[ch].process(&self.buffers[ch], head.read_pos, grain_len, grain_xfade, params.hold_repeats)
+        } else {
+            self.holds[ch].release();
+            None
+        };
+        let mut tape_sound = match held {
+            Some(sample) => sample,
+            None => self.buffers[ch].read(head.read_pos),
         };
 
-        // フィルター係数の計算
-        if enable_filter {
+        // テープ飽和（リンク時は速度が落ちるほど強く歪ませる）
+        if params.enable_saturation {
+            let drive = if params.saturation_link {
+                params.saturation_drive + (1.0 - params.saturation_drive) * slowdown
+            } else {
+                params.saturation_drive
+            };
+            tape_sound = S::from_f32(self.saturators[ch].process(
+                tape_sound.to_f32(),
+                drive,
+                params.saturation_bias,
+                params.saturation_mix,
+            ));
+        }
+
+        // フィルター適用
+        if params.enable_filter {
             // 速度に応じてカットオフを変化させる
-            // 速度が低いほどこもらせる
-            let min_cut: f32 = 200.0;
-            let max_cut: f32 = 20000.0;
-            let cutoff = min_cut * (max_cut / min_cut).powf(self.current_speed as f32);
+            self.filters[ch].set_cutoff(filter_cutoff(speed), self.sample_rate);
+            tape_sound = self.filters[ch].process(tape_sound);
+        }
+
+        // ローファイ処理（速度が落ちるほど強くする）
+        if params.enable_decimate {
+            tape_sound = S::from_f32(self.degraders[ch].decimate(
+                tape_sound.to_f32(),
+                params.decimate_amount * slowdown,
+                params.lofi_anti_alias,
+                self.sample_rate,
+            ));
+        }
+        if params.enable_bitcrush {
+            tape_sound = S::from_f32(self.degraders[ch].crush(tape_sound.to_f32(), params.bitcrush_amount * slowdown));
+        }
+
+        // ノイズ（テープ音と一緒にクロスフェードされるので、通常再生中は聞こえない）
+        if params.enable_noise {
+            tape_sound += S::from_f32(self.noises[ch].process(
+                head.current_speed,
+                params.hiss_level,
+                params.crackle_level,
+                params.motor_level,
+                self.sample_rate,
+            ));
+        }
+
+        // クロスフェード
+        let crossfade_gain = S::from_f32(head.crossfade_gain);
+        tape_sound * (S::ONE - crossfade_gain) + in_sample * crossfade_gain
+    }
+}
 
-            for f in &mut self.filters {
-                f.set_cutoff(cutoff, self.sample_rate);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 1_000.0;
+
+    fn run(engine: &mut TapeStopEngine, params: &EngineParams, samples: usize) {
+        let input = [0.5f32; 2];
+        let mut output = [0.0f32; 2];
+        for _ in 0..samples {
+            engine.process(&input, &mut output, params);
+        }
+    }
+
+    #[test]
+    fn state_follows_stop_and_start_ramps() {
+        let mut engine = TapeStopEngine::new(SAMPLE_RATE, 3.0, 2);
+        let mut params = EngineParams {
+            stop_time_sec: 0.5,
+            start_time_sec: 0.5,
+            ..EngineParams::default()
+        };
+        assert_eq!(engine.state(), TapeState::Playing);
+
+        params.trigger = true;
+        run(&mut engine, &params, 10);
+        assert_eq!(engine.state(), TapeState::Stopping);
+
+        run(&mut engine, &params, 500);
+        assert_eq!(engine.state(), TapeState::Stopped);
+
+        params.trigger = false;
+        run(&mut engine, &params, 10);
+        assert_eq!(engine.state(), TapeState::Starting);
+
+        run(&mut engine, &params, 500);
+        assert_eq!(engine.state(), TapeState::Crossfading);
+
+        run(&mut engine, &params, (CROSSFADE_SEC * SAMPLE_RATE) as usize + 1);
+        assert_eq!(engine.state(), TapeState::Playing);
+        assert_eq!(engine.tail_samples(), 0);
+    }
+
+    #[test]
+    fn tail_samples_covers_the_remaining_start_ramp() {
+        let mut engine = TapeStopEngine::new(SAMPLE_RATE, 3.0, 2);
+        let mut params = EngineParams {
+            trigger: true,
+            stop_time_sec: 0.2,
+            start_time_sec: 0.4,
+            ..EngineParams::default()
+        };
+        run(&mut engine, &params, 300);
+        assert_eq!(engine.state(), TapeState::Stopped);
+
+        params.trigger = false;
+        run(&mut engine, &params, 1);
+        let tail = engine.tail_samples() as usize;
+        let expected = (0.4 * SAMPLE_RATE) as usize + (CROSSFADE_SEC * SAMPLE_RATE) as usize;
+        assert!(tail.abs_diff(expected) <= 2, "tail = {tail}, expected ≈ {expected}");
+
+        run(&mut engine, &params, tail);
+        assert_eq!(engine.state(), TapeState::Playing);
+    }
+
+    #[test]
+    fn reset_returns_to_playing() {
+        let mut engine = TapeStopEngine::new(SAMPLE_RATE, 3.0, 2);
+        let params = EngineParams {
+            trigger: true,
+            ..EngineParams::default()
+        };
+        run(&mut engine, &params, 100);
+        assert_ne!(engine.state(), TapeState::Playing);
+
+        engine.reset();
+        assert_eq!(engine.state(), TapeState::Playing);
+    }
+
+    #[test]
+    fn spread_staggers_left_and_right_heads() {
+        let mut engine = TapeStopEngine::new(SAMPLE_RATE, 3.0, 2);
+        let params = EngineParams {
+            trigger: true,
+            stop_time_sec: 0.5,
+            stereo_spread: 1.0,
+            ..EngineParams::default()
+        };
+        run(&mut engine, &params, 250);
+
+        // 左は早く、右は遅く止まる
+        let (left, right) = (engine.heads[0], engine.heads[1]);
+        assert!(left.phase < right.phase);
+        assert!(left.current_speed < right.current_speed);
+
+        // 一番遅いヘッドが止まるまでは Stopping のまま
+        run(&mut engine, &params, 200);
+        assert_eq!(engine.heads[0].phase, 0.0);
+        assert_eq!(engine.state(), TapeState::Stopping);
+        run(&mut engine, &params, 300);
+        assert_eq!(engine.state(), TapeState::Stopped);
+    }
+
+    #[test]
+    fn spread_only_moves_the_front_pair() {
+        let positions: Vec<f64> = (0..6).map(|ch| spread_position(ch, 6)).collect();
+        assert_eq!(positions, vec![-1.0, 1.0, 0.0, 0.0, 0.0, 0.0]);
+        assert_eq!(spread_position(0, 1), 0.0);
+
+        // 5.1 では C / LFE / リアのヘッドがそろったまま止まる
+        let mut engine = TapeStopEngine::new(SAMPLE_RATE, 3.0, 6);
+        let params = EngineParams {
+            trigger: true,
+            stop_time_sec: 0.5,
+            stereo_spread: 1.0,
+            ..EngineParams::default()
+        };
+        run(&mut engine, &params, 250);
+        assert!(engine.heads[0].phase < engine.heads[1].phase);
+        for ch in 3..6 {
+            assert_eq!(engine.heads[ch].read_pos, engine.heads[2].read_pos);
+        }
+    }
+
+    #[test]
+    fn zero_spread_keeps_heads_locked() {
+        let mut engine = TapeStopEngine::new(SAMPLE_RATE, 3.0, 2);
+        let params = EngineParams {
+            trigger: true,
+            ..EngineParams::default()
+        };
+        run(&mut engine, &params, 250);
+        assert_eq!(engine.heads[0].read_pos, engine.heads[1].read_pos);
+        assert_eq!(engine.heads[0].current_speed, engine.heads[1].current_speed);
+    }
+
+    #[test]
+    fn width_collapses_to_mono_when_stopped() {
+        let mut engine = TapeStopEngine::new(SAMPLE_RATE, 3.0, 2);
+        let params = EngineParams {
+            trigger: true,
+            stop_time_sec: 0.1,
+            enable_filter: false,
+            enable_width: true,
+            width_at_stop: 0.0,
+            ..EngineParams::default()
+        };
+        let mut output = [0.0f32; 2];
+        for _ in 0..200 {
+            engine.process(&[0.8, -0.2], &mut output, &params);
+        }
+        assert_eq!(engine.state(), TapeState::Stopped);
+        assert!((output[0] - output[1]).abs() < 1.0e-6);
+    }
+
+    #[test]
+    fn width_is_ignored_for_mono() {
+        let mut engine = TapeStopEngine::new(SAMPLE_RATE, 3.0, 1);
+        let params = EngineParams {
+            enable_width: true,
+            ..EngineParams::default()
+        };
+        let mut output = [0.0f32; 1];
+        engine.process(&[0.25], &mut output, &params);
+        assert_eq!(output[0], 0.25);
+    }
+
+    #[test]
+    fn noise_is_only_heard_while_the_tape_is_engaged() {
+        let mut engine = TapeStopEngine::new(SAMPLE_RATE, 3.0, 2);
+        let mut params = EngineParams {
+            enable_noise: true,
+            hiss_level: 1.0,
+            crackle_level: 1.0,
+            motor_level: 1.0,
+            ..EngineParams::default()
+        };
+        let mut output = [0.0f32; 2];
+        for _ in 0..100 {
+            engine.process(&[0.0, 0.0], &mut output, &params);
+            assert_eq!(output, [0.0, 0.0]);
+        }
+
+        params.trigger = true;
+        let mut energy = 0.0;
+        for _ in 0..100 {
+            engine.process(&[0.0, 0.0], &mut output, &params);
+            energy += output[0].abs() + output[1].abs();
+        }
+        assert!(energy > 0.0);
+    }
+
+    #[test]
+    fn hold_loops_the_grain_before_the_stop_point() {
+        let mut engine = TapeStopEngine::new(SAMPLE_RATE, 3.0, 1);
+        let params = EngineParams {
+            trigger: true,
+            stop_time_sec: 0.1,
+            enable_filter: false,
+            enable_hold: true,
+            hold_division: HoldDivision::Quarter,
+            hold_crossfade: 0.0,
+            hold_repeats: 2,
+            bpm: 600.0, // 1/4 = 100 サンプル
+            ..EngineParams::default()
+        };
+
+        // 入力にランプを流しておき、停止後の出力からどこを読んでいるか分かるようにする
+        let mut output = [0.0f32; 1];
+        let mut n = 0.0f32;
+        let idle = EngineParams { trigger: false, ..params };
+        for _ in 0..500 {
+            engine.process(&[n], &mut output, &idle);
+            n += 1.0;
+        }
+        while engine.state() != TapeState::Stopped {
+            engine.process(&[n], &mut output, &params);
+            n += 1.0;
+        }
+
+        // 止まったサンプルからループが始まっている
+        let mut looped = vec![output[0]];
+        for _ in 1..200 {
+            engine.process(&[n], &mut output, &params);
+            n += 1.0;
+            looped.push(output[0]);
+        }
+        let (first, second) = looped.split_at(100);
+        assert_eq!(first, second);
+        assert!(first.windows(2).all(|w| w[1] > w[0]));
+
+        // ループ回数を使い切ったら通常の停止状態に戻る
+        engine.process(&[n], &mut output, &params);
+        let stopped = output[0];
+        engine.process(&[n + 1.0], &mut output, &params);
+        assert_eq!(output[0], stopped);
+    }
+
+    #[test]
+    fn pitch_linear_stops_in_the_same_time_as_other_curves() {
+        let stop_samples = |curve: TapeCurve| {
+            let mut engine = TapeStopEngine::new(SAMPLE_RATE, 3.0, 2);
+            let params = EngineParams {
+                trigger: true,
+                stop_time_sec: 0.3,
+                curve,
+                ..EngineParams::default()
+            };
+            let mut samples = 0;
+            while engine.state() != TapeState::Stopped {
+                run(&mut engine, &params, 1);
+                samples += 1;
+            }
+            samples
+        };
+
+        let linear = stop_samples(TapeCurve::Linear);
+        assert_eq!(stop_samples(TapeCurve::PitchLinear), linear);
+        assert_eq!(stop_samples(TapeCurve::QuickCut), linear);
+    }
+
+    #[test]
+    fn stop_distance_does_not_depend_on_sample_rate() {
+        // 停止までに進んだ距離（秒）
+        let stop_distance = |sample_rate: f32, curve: TapeCurve| {
+            let mut engine = TapeStopEngine::new(sample_rate, 3.0, 1);
+            let mut output = [0.0f32; 1];
+            let params = EngineParams {
+                trigger: true,
+                stop_time_sec: 0.7,
+                curve,
+                ..EngineParams::default()
+            };
+            let origin = engine.heads[0].read_pos;
+            while engine.state() != TapeState::Stopped {
+                engine.process(&[0.0], &mut output, &params);
+            }
+            (engine.heads[0].read_pos - origin) / sample_rate as f64
+        };
+
+        for curve in [TapeCurve::Linear, TapeCurve::Smooth, TapeCurve::QuickCut, TapeCurve::PitchLinear] {
+            let expected = 0.7 * curve_area(curve, 0.0, 1.0, 1.0, -36.0);
+            for sample_rate in [44_100.0, 48_000.0, 96_000.0] {
+                let distance = stop_distance(sample_rate, curve);
+                assert!((distance - expected).abs() < 1.0e-6, "{curve:?} @ {sample_rate}: {distance} vs {expected}");
             }
         }
+    }
 
-        // 音声処理
-        for (ch, (&in_sample, out_sample)) in input.iter().zip(output.iter_mut()).enumerate() {
-            if ch >= self.buffers.len() { break; }
+    #[test]
+    fn land_on_beat_consumes_exactly_one_sync_length() {
+        for curve in [TapeCurve::Linear, TapeCurve::SlowStart, TapeCurve::PitchLinear] {
+            let mut engine = TapeStopEngine::new(SAMPLE_RATE, 3.0, 1);
+            let mut output = [0.0f32; 1];
+            let params = EngineParams {
+                trigger: true,
+                use_sync: true,
+                sync_beat: SyncBeat::Quarter,
+                bpm: 120.0,
+                land_on_beat: true,
+                curve,
+                ..EngineParams::default()
+            };
+            while engine.state() != TapeState::Stopped {
+                engine.process(&[0.0], &mut output, &params);
+            }
 
-            // 書き込み
-            self.buffers[ch].write(self.write_pos, in_sample);
+            // 120 BPM の 1/4 = 0.5 秒 = 500 サンプル
+            let distance = engine.heads[0].read_pos;
+            assert!((distance - 500.0).abs() < 1.0e-6, "{curve:?}: {distance}");
+        }
+    }
 
-            // 読み込み
-            let mut tape_sound = self.buffers[ch].read(self.read_pos);
+    #[test]
+    fn f64_engine_passes_full_precision_when_idle() {
+        let mut engine = TapeStopEngine::<f64>::new(SAMPLE_RATE, 3.0, 1);
+        let params = EngineParams::default();
+        let mut output = [0.0f64];
+        for n in 0..100 {
+            // f32 では表せない細かさの値がそのまま出てくるはず
+            let input = [0.1 + n as f64 * 1.0e-12];
+            engine.process(&input, &mut output, &params);
+            assert_eq!(output[0], input[0]);
+        }
+    }
 
-            // フィルター適用
-            if enable_filter {
-                tape_sound = self.filters[ch].process(tape_sound);
+    #[test]
+    fn f64_engine_tracks_f32_engine_through_a_stop() {
+        let mut single = TapeStopEngine::<f32>::new(SAMPLE_RATE, 3.0, 1);
+        let mut double = TapeStopEngine::<f64>::new(SAMPLE_RATE, 3.0, 1);
+        let mut params = EngineParams {
+            curve: TapeCurve::Smooth,
+            ..EngineParams::default()
+        };
+
+        let mut out_single = [0.0f32];
+        let mut out_double = [0.0f64];
+        for n in 0..1_500 {
+            params.trigger = (200..800).contains(&n);
+            let x = (n as f64 * 0.05).sin() * 0.5;
+            single.process(&[x as f32], &mut out_single, &params);
+            double.process(&[x], &mut out_double, &params);
+            let diff = (out_single[0] as f64 - out_double[0]).abs();
+            assert!(diff < 1.0e-4, "sample {n}: diff {diff}");
+        }
+    }
+
+    #[test]
+    fn simd_path_matches_scalar_path() {
+        let channels = 7; // レーン数で割り切れない端数も確かめる
+        let mut simd = TapeStopEngine::<f32>::new(SAMPLE_RATE, 3.0, channels);
+        let mut scalar = TapeStopEngine::<f32>::new(SAMPLE_RATE, 3.0, channels);
+        scalar.set_simd(false);
+
+        let mut params = EngineParams {
+            curve: TapeCurve::Smooth,
+            stop_time_sec: 0.3,
+            start_time_sec: 0.2,
+            enable_wow: true,
+            ..EngineParams::default()
+        };
+
+        let mut input = vec![0.0f32; channels];
+        let mut out_simd = vec![0.0f32; channels];
+        let mut out_scalar = vec![0.0f32; channels];
+        for n in 0..1_200 {
+            params.trigger = (100..600).contains(&n);
+            for (ch, x) in input.iter_mut().enumerate() {
+                *x = ((n * (ch + 1)) as f32 * 0.01).sin() * 0.5;
             }
+            simd.process(&input, &mut out_simd, &params);
+            scalar.process(&input, &mut out_scalar, &params);
+            for (a, b) in out_simd.iter().zip(out_scalar.iter()) {
+                assert!((a - b).abs() < 1.0e-6, "sample {n}: {a} vs {b}");
+            }
+        }
+    }
+
+    #[test]
+    fn set_sample_rate_restarts_cleanly_at_the_new_rate() {
+        let mut engine = TapeStopEngine::<f32>::new(SAMPLE_RATE, 3.0, 2);
+        let mut params = EngineParams {
+            trigger: true,
+            stop_time_sec: 0.5,
+            ..EngineParams::default()
+        };
+        run(&mut engine, &params, 200);
+        assert_eq!(engine.state(), TapeState::Stopping);
+
+        // ランプの途中で変更しても、通常再生の状態から始まる
+        engine.set_sample_rate(2.0 * SAMPLE_RATE);
+        assert_eq!(engine.sample_rate(), 2.0 * SAMPLE_RATE);
+        assert_eq!(engine.state(), TapeState::Playing);
+        assert_eq!(engine.tail_samples(), 0);
+
+        params.trigger = false;
+        let mut output = [0.0f32; 2];
+        engine.process(&[0.25, -0.25], &mut output, &params);
+        assert_eq!(output, [0.25, -0.25]);
+
+        // 停止時間は新しいサンプルレートで数える
+        params.trigger = true;
+        run(&mut engine, &params, 900);
+        assert_eq!(engine.state(), TapeState::Stopping);
+        run(&mut engine, &params, 200);
+        assert_eq!(engine.state(), TapeState::Stopped);
+    }
+
+    #[test]
+    fn set_max_seconds_resizes_the_buffers() {
+        let mut engine = TapeStopEngine::<f32>::new(SAMPLE_RATE, 0.5, 2);
+        assert_eq!(engine.buffers[0].capacity(), 512);
+
+        engine.set_max_seconds(3.0);
+        assert!(engine.buffers.iter().all(|b| b.capacity() == 4096));
 
-            // クロスフェード出力
-            *out_sample = tape_sound * (1.0 - self.crossfade_gain) + in_sample * self.crossfade_gain;
+        // 縮めた後も読み書きが有効な範囲に収まる
+        engine.set_max_seconds(0.1);
+        assert!(engine.buffers.iter().all(|b| b.capacity() == 128));
+        let params = EngineParams {
+            trigger: true,
+            stop_time_sec: 0.05,
+            ..EngineParams::default()
+        };
+        run(&mut engine, &params, 100);
+        assert_eq!(engine.state(), TapeState::Stopped);
+    }
+
+    /**
+     * 止めてから再始動し終わるまで回し、最大の遅れ（サンプル）と切り詰めが起きたかどうかを返す
+     */
+    fn stop_and_restart(engine: &mut TapeStopEngine<f32>, params: &EngineParams) -> (f64, bool) {
+        let mut params = *params;
+        let mut max_lag: f64 = 0.0;
+        let mut clamped = false;
+        let mut step = |engine: &mut TapeStopEngine<f32>, params: &EngineParams| {
+            run(engine, params, 1);
+            clamped |= engine.is_clamped();
+            for head in &engine.heads {
+                max_lag = max_lag.max(engine.write_pos as f64 - head.read_pos);
+            }
+        };
+        params.trigger = true;
+        while engine.state() != TapeState::Stopped {
+            step(engine, &params);
+        }
+        params.trigger = false;
+        while engine.state() != TapeState::Playing {
+            step(engine, &params);
         }
+        (max_lag, clamped)
+    }
 
-        // ヘッド進行
-        self.write_pos = self.write_pos.wrapping_add(1);
-        self.read_pos += self.current_speed;
+    #[test]
+    fn required_seconds_fits_the_longest_plain_synced_stop() {
+        let seconds = TapeStopEngine::<f32>::required_seconds(MIN_SYNC_BPM);
+        let mut engine = TapeStopEngine::<f32>::new(SAMPLE_RATE, seconds, 2);
+        let params = EngineParams {
+            use_sync: true,
+            sync_beat: SyncBeat::TwoBars,
+            bpm: MIN_SYNC_BPM,
+            curve: TapeCurve::QuickCut,
+            start_time_sec: MAX_RAMP_SEC,
+            ..EngineParams::default()
+        };
+
+        // 最低テンポの 2 Bars は切り詰めずに止まり、遅れはずっとバッファの中に収まる
+        let (max_lag, clamped) = stop_and_restart(&mut engine, &params);
+        assert!(!clamped);
+        assert!(max_lag / (SAMPLE_RATE as f64) < seconds as f64, "lag {max_lag} samples vs {seconds} s");
+    }
+
+    #[test]
+    fn extreme_stops_are_clamped_to_the_capped_buffer() {
+        let seconds = TapeStopEngine::<f32>::required_seconds(MIN_SYNC_BPM);
+        assert_eq!(seconds, (MAX_LAG_SEC + CAPACITY_MARGIN_SEC) as f32);
+        assert!(worst_case_lag_seconds(MIN_SYNC_BPM) > seconds as f64);
+
+        let mut engine = TapeStopEngine::<f32>::new(SAMPLE_RATE, seconds, 2);
+        let params = EngineParams {
+            use_sync: true,
+            sync_beat: SyncBeat::TwoBars,
+            bpm: MIN_SYNC_BPM,
+            land_on_beat: true,
+            curve: TapeCurve::PitchLinear,
+            pitch_floor_st: MIN_PITCH_FLOOR_ST,
+            start_time_sec: MAX_RAMP_SEC,
+            ..EngineParams::default()
+        };
+
+        // 上限を超える組み合わせは確保し直さずに切り詰め、それを知らせる
+        let (max_lag, clamped) = stop_and_restart(&mut engine, &params);
+        assert!(clamped);
+        assert!(max_lag / (SAMPLE_RATE as f64) < seconds as f64, "lag {max_lag} samples vs {seconds} s");
+    }
+
+    #[test]
+    fn synced_stop_longer_than_the_buffer_is_clamped() {
+        let mut engine = TapeStopEngine::<f32>::new(SAMPLE_RATE, 1.0, 1);
+        let params = EngineParams {
+            trigger: true,
+            use_sync: true,
+            sync_beat: SyncBeat::TwoBars,
+            bpm: 60.0, // 8 秒
+            start_time_sec: 0.2,
+            ..EngineParams::default()
+        };
+
+        run(&mut engine, &params, 1);
+        assert!(engine.is_clamped());
+
+        // バッファ（1 秒）に収まる遅れで止まりきる
+        let mut samples = 1;
+        while engine.state() != TapeState::Stopped {
+            run(&mut engine, &params, 1);
+            samples += 1;
+        }
+        let lag = engine.write_pos as f64 - engine.heads[0].read_pos;
+        assert!(samples < 2 * SAMPLE_RATE as usize, "took {samples} samples");
+        assert!(lag < SAMPLE_RATE as f64, "lag {lag}");
+    }
+
+    #[test]
+    fn synced_stop_length_is_locked_at_trigger_time() {
+        let mut engine = TapeStopEngine::<f32>::new(SAMPLE_RATE, 4.0, 2);
+        let mut params = EngineParams {
+            trigger: true,
+            use_sync: true,
+            sync_beat: SyncBeat::Quarter,
+            bpm: 120.0, // 0.5 秒
+            start_time_sec: 0.1,
+            ..EngineParams::default()
+        };
+
+        // 停止の途中でテンポが半分になっても、トリガー時の長さで止まる
+        run(&mut engine, &params, 100);
+        params.bpm = 60.0;
+        let mut samples: usize = 100;
+        while engine.state() != TapeState::Stopped {
+            run(&mut engine, &params, 1);
+            samples += 1;
+        }
+        assert!(samples.abs_diff(500) <= 2, "stopped after {samples} samples");
+
+        // 離してから再びトリガーすると、新しいテンポで長さが決まる
+        params.trigger = false;
+        while engine.state() != TapeState::Playing {
+            run(&mut engine, &params, 1);
+        }
+        params.trigger = true;
+        let mut samples: usize = 0;
+        while engine.state() != TapeState::Stopped {
+            run(&mut engine, &params, 1);
+            samples += 1;
+        }
+        assert!(samples.abs_diff(1000) <= 2, "stopped after {samples} samples");
     }
 }
\ No newline at end of file
@@ -1,14 +1,22 @@
-﻿#[derive(Clone, Copy)]
-pub struct OnePoleLowpass {
-    prev_output: f32,
-    alpha: f32,
+﻿use super::sample::{Sample, SampleLanes, LANES};
+
+#[derive(Clone, Copy)]
+pub struct OnePoleLowpass<S: Sample = f32> {
+    prev_output: S,
+    alpha: S,
 }
 
-impl OnePoleLowpass {
+impl<S: Sample> Default for OnePoleLowpass<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: Sample> OnePoleLowpass<S> {
     pub fn new () -> Self {
         Self {
-            prev_output: 0.0,
-            alpha: 1.0,
+            prev_output: S::ZERO,
+            alpha: S::ONE,
         }
     }
 
@@ -16,24 +24,52 @@ impl OnePoleLowpass {
      * カットオフ周波数を設定する
      */
     pub fn set_cutoff(&mut self, cutoff_hz: f32, sample_rate: f32) {
-        let y = 1.0 - (-2.0 * std::f32::consts::PI * cutoff_hz / sample_rate).exp();
-        self.alpha = y.clamp(0.0, 1.0);
+        self.alpha = Self::coefficient(cutoff_hz, sample_rate);
+    }
+
+    /**
+     * カットオフ周波数から係数を求める
+     */
+    pub fn coefficient(cutoff_hz: f32, sample_rate: f32) -> S {
+        let y = S::ONE - S::from_f64(-2.0 * std::f64::consts::PI * cutoff_hz as f64 / sample_rate as f64).exp();
+        y.clamp(S::ZERO, S::ONE)
     }
 
     /**
      * フィルター処理を行う
      */
-    pub fn process(&mut self, input: f32) -> f32 {
+    pub fn process(&mut self, input: S) -> S {
         let output = self.prev_output + self.alpha * (input - self.prev_output);
         self.prev_output = output;
         output
     }
 
+    /**
+     * 同じ係数の複数のフィルタをまとめて処理する（SIMD）
+     * filters は LANES 本まで。余ったレーンの結果は捨てられる
+     */
+    #[inline]
+    pub fn process_lanes(filters: &mut [OnePoleLowpass<S>], alpha: S, input: S::Lanes) -> S::Lanes {
+        let mut prev = [S::ZERO; LANES];
+        for (lane, filter) in filters.iter().enumerate().take(LANES) {
+            prev[lane] = filter.prev_output;
+        }
+
+        let prev = S::Lanes::from_array(prev);
+        let output = prev + S::Lanes::splat(alpha) * (input - prev);
+
+        for (filter, out) in filters.iter_mut().zip(output.to_array()) {
+            filter.prev_output = out;
+            filter.alpha = alpha;
+        }
+        output
+    }
+
     /**
      * フィルター状態をリセット
      */
     pub fn reset(&mut self) {
-        self.prev_output = 0.0;
-        self.alpha = 1.0;
+        self.prev_output = S::ZERO;
+        self.alpha = S::ONE;
     }
 }
\ No newline at end of file
@@ -0,0 +1,86 @@
+use super::buffer::DelayLine;
+use super::sample::Sample;
+
+#[derive(Clone, Copy)]
+pub struct GrainHold {
+    active: bool,   // ループ中かどうか
+    anchor: f64,    // 停止した位置（グレインの終端）
+    position: f64,  // グレイン内の再生位置（サンプル）
+    repeats: u32,   // ループした回数
+}
+
+impl Default for GrainHold {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GrainHold {
+    pub fn new() -> Self {
+        Self {
+            active: false,
+            anchor: 0.0,
+            position: 0.0,
+            repeats: 0,
+        }
+    }
+
+    /**
+     * 停止位置の手前のグレインをループ再生する
+     * ループが終わった後は None を返すので、呼び出し側で通常の読み込みに戻すこと
+     * - buffer 読み込むバッファ
+     * - read_pos 停止したヘッドの位置
+     * - grain_len グレインの長さ（サンプル）
+     * - xfade_len ループのつなぎ目のクロスフェード長（サンプル）
+     * - max_repeats ループ回数 (0 = 無限)
+     */
+    pub fn process<S: Sample>(
+        &mut self,
+        buffer: &DelayLine<S>,
+        read_pos: f64,
+        grain_len: f64,
+        xfade_len: f64,
+        max_repeats: u32,
+    ) -> Option<S> {
+        if !self.active {
+            self.active = true;
+            self.anchor = read_pos;
+            self.position = 0.0;
+            self.repeats = 0;
+        }
+
+        if max_repeats > 0 && self.repeats >= max_repeats {
+            return None;
+        }
+
+        let grain_len = grain_len.max(1.0);
+        let xfade_len = xfade_len.clamp(0.0, grain_len * 0.5);
+        let start = self.anchor - grain_len;
+
+        // グレイン本体
+        let mut sample = buffer.read(start + self.position);
+
+        // 終端ではグレインの手前の音へクロスフェードし、先頭へ戻るときに途切れないようにする
+        let fade_start = grain_len - xfade_len;
+        if xfade_len > 0.0 && self.position > fade_start {
+            let fade = S::from_f64((self.position - fade_start) / xfade_len);
+            let pre_roll = buffer.read(start + self.position - grain_len);
+            sample = sample * (S::ONE - fade) + pre_roll * fade;
+        }
+
+        self.position += 1.0;
+        if self.position >= grain_len {
+            self.position -= grain_len;
+            self.repeats += 1;
+        }
+
+        Some(sample)
+    }
+
+    /**
+     * ループを解除
+     */
+    pub fn release(&mut self) {
+        self.active = false;
+    }
+}
\ No newline at end of file
@@ -0,0 +1,37 @@
+use super::sample::Sample;
+
+#[derive(Clone, Copy)]
+pub struct SoftClipper {
+    knee: f32,
+}
+
+impl Default for SoftClipper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SoftClipper {
+    pub fn new() -> Self {
+        Self {
+            knee: 0.8,
+        }
+    }
+
+    /**
+     * ソフトクリップ処理を行う
+     * knee 以下はそのまま通し、それを超えた分は 1.0 に向かって滑らかに飽和させる
+     */
+    #[inline]
+    pub fn process<S: Sample>(&self, input: S) -> S {
+        let abs = input.abs();
+        let knee = S::from_f32(self.knee);
+        if abs <= knee {
+            return input;
+        }
+
+        let range = S::from_f32(1.0 - self.knee);
+        let shaped = knee + range * ((abs - knee) / range).tanh();
+        shaped.copysign(input)
+    }
+}
\ No newline at end of file
@@ -0,0 +1,140 @@
+use super::filter::OnePoleLowpass;
+
+// 強度 1.0 のときの最大間引き率
+const MAX_DECIMATION: f32 = 32.0;
+// 強度 0.0 -> 1.0 で変化させるビット深度
+const MAX_BITS: f32 = 16.0;
+const MIN_BITS: f32 = 3.0;
+
+#[derive(Clone, Copy)]
+pub struct LofiDegrader {
+    anti_alias: [OnePoleLowpass; 2], // 間引き前のエイリアス除去フィルタ
+    hold_phase: f32,                 // サンプル&ホールドの進行度
+    held: f32,                       // 保持中のサンプル
+}
+
+impl Default for LofiDegrader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LofiDegrader {
+    pub fn new() -> Self {
+        Self {
+            anti_alias: [OnePoleLowpass::new(); 2],
+            hold_phase: 0.0,
+            held: 0.0,
+        }
+    }
+
+    /**
+     * サンプルレートを擬似的に下げる
+     * - input 入力サンプル
+     * - intensity 強度 (0.0 -> 1.0)。1.0 で 1/32 まで間引く
+     * - anti_alias 間引き前にローパスをかけるかどうか
+     * - sample_rate 本来のサンプルレート
+     */
+    #[inline]
+    pub fn decimate(&mut self, input: f32, intensity: f32, anti_alias: bool, sample_rate: f32) -> f32 {
+        let factor = 1.0 + intensity.clamp(0.0, 1.0) * (MAX_DECIMATION - 1.0);
+        if factor <= 1.0 {
+            // 間引かないときはフィルタも通さずにそのまま返す
+            // 強度を上げたときに飛ばないよう、フィルタと保持の状態だけは入力に合わせて進めておく
+            for filter in &mut self.anti_alias {
+                filter.set_cutoff(0.5 * sample_rate, sample_rate);
+                filter.process(input);
+            }
+            self.hold_phase = 0.0;
+            self.held = input;
+            return input;
+        }
+
+        let mut sample = input;
+        if anti_alias {
+            // 間引き後のナイキスト周波数でカット
+            let cutoff = 0.5 * sample_rate / factor;
+            for filter in &mut self.anti_alias {
+                filter.set_cutoff(cutoff, sample_rate);
+                sample = filter.process(sample);
+            }
+        }
+
+        self.hold_phase += 1.0;
+        if self.hold_phase >= factor {
+            self.hold_phase -= factor;
+            self.held = sample;
+        }
+        self.held
+    }
+
+    /**
+     * ビット深度を下げる
+     * - input 入力サンプル
+     * - intensity 強度 (0.0 -> 1.0)。1.0 で 3 bit まで落とす
+     */
+    #[inline]
+    pub fn crush(&self, input: f32, intensity: f32) -> f32 {
+        let intensity = intensity.clamp(0.0, 1.0);
+        if intensity <= 0.0 {
+            return input;
+        }
+
+        let bits = MAX_BITS - intensity * (MAX_BITS - MIN_BITS);
+        let levels = 2.0f32.powf(bits - 1.0);
+        // 段の数が整数でないとフルスケール付近で丸めた値が 1.0 を超えるので、そこで抑える
+        let limit = input.abs().max(1.0);
+        ((input * levels).round() / levels).clamp(-limit, limit)
+    }
+
+    /**
+     * 状態をリセット
+     */
+    pub fn reset(&mut self) {
+        for filter in &mut self.anti_alias {
+            filter.reset();
+        }
+        self.hold_phase = 0.0;
+        self.held = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zero_intensity_leaves_the_signal_unchanged() {
+        let mut lofi = LofiDegrader::new();
+        for i in 0..10_000 {
+            let input = (i as f32 * 0.05).sin();
+            assert_eq!(lofi.decimate(input, 0.0, false, 48_000.0), input);
+            assert_eq!(lofi.decimate(input, 0.0, true, 48_000.0), input);
+            assert_eq!(lofi.crush(input, 0.0), input);
+        }
+    }
+
+    #[test]
+    fn held_sample_repeats_at_the_decimation_rate() {
+        // 強度 1.0 で 1/32 に間引くので、32 サンプルごとにその時点の入力を拾って保持する
+        let mut lofi = LofiDegrader::new();
+        let output: Vec<f32> = (0..32 * 4).map(|i| lofi.decimate(i as f32, 1.0, false, 48_000.0)).collect();
+
+        assert!(output[..31].iter().all(|&value| value == 0.0));
+        for (i, &value) in output.iter().enumerate().skip(31) {
+            assert_eq!(value, (31 + (i - 31) / 32 * 32) as f32, "sample {i}");
+        }
+    }
+
+    #[test]
+    fn output_stays_bounded() {
+        let mut lofi = LofiDegrader::new();
+        for i in 0..48_000 {
+            let input = if (i / 7) % 2 == 0 { 1.0 } else { -1.0 };
+            let intensity = (i % 1_000) as f32 / 999.0;
+            let decimated = lofi.decimate(input, intensity, true, 48_000.0);
+            let crushed = lofi.crush(decimated, intensity);
+            assert!(decimated.abs() <= 1.0 && crushed.abs() <= 1.0, "{i}: {decimated} / {crushed}");
+        }
+    }
+}
\ No newline at end of file
@@ -1,3 +1,14 @@
 pub mod buffer;
+pub mod curve;
 pub mod engine;
-pub mod filter;
\ No newline at end of file
+pub mod filter;
+pub mod hold;
+pub mod limiter;
+pub mod lofi;
+pub mod noise;
+pub mod oversampling;
+pub mod sample;
+pub mod saturation;
+pub mod sync;
+pub mod width;
+pub mod wow;
\ No newline at end of file
@@ -0,0 +1,137 @@
+use std::f64::consts::TAU;
+
+// 各ノイズのレベル 1.0 のときの最大振幅
+const HISS_GAIN: f32 = 0.05;
+const CRACKLE_GAIN: f32 = 0.5;
+const MOTOR_GAIN: f32 = 0.05;
+// クラックルの平均発生回数（回/秒）
+const CRACKLE_RATE: f32 = 6.0;
+// クラックル1発の減衰時間（秒）
+const CRACKLE_DECAY_SEC: f32 = 0.002;
+// 通常速度でのモーター音の基本周波数 (Hz)
+const MOTOR_HZ: f64 = 120.0;
+
+#[derive(Clone, Copy)]
+pub struct NoiseGenerator {
+    seed: u32,
+    rng: u32,
+    hiss_lowpass: f32,  // ヒスの低域を削るためのローパス状態
+    crackle_env: f32,   // クラックルのエンベロープ
+    motor_phase: f64,   // モーター音の位相 (0.0 -> 1.0)
+}
+
+impl NoiseGenerator {
+    pub fn new(seed: u32) -> Self {
+        Self {
+            seed,
+            rng: Self::initial_state(seed),
+            hiss_lowpass: 0.0,
+            crackle_env: 0.0,
+            motor_phase: 0.0,
+        }
+    }
+
+    /**
+     * 状態をリセット（同じシードから乱数を引き直す）
+     */
+    pub fn reset(&mut self) {
+        *self = Self::new(self.seed);
+    }
+
+    /**
+     * ノイズを1サンプル生成
+     * - speed テープ速度 (0.0 -> 1.0)。モーター音の高さと大きさが追従する
+     * - hiss_level テープヒスの量 (0.0 -> 1.0)
+     * - crackle_level クラックルの量 (0.0 -> 1.0)
+     * - motor_level モーター音の量 (0.0 -> 1.0)
+     * - sample_rate サンプルレート
+     */
+    pub fn process(
+        &mut self,
+        speed: f64,
+        hiss_level: f32,
+        crackle_level: f32,
+        motor_level: f32,
+        sample_rate: f32,
+    ) -> f32 {
+        let mut out = 0.0;
+
+        // テープヒス: ホワイトノイズから低域を削ったもの
+        let white = self.next_random();
+        self.hiss_lowpass += 0.1 * (white - self.hiss_lowpass);
+        out += (white - self.hiss_lowpass) * hiss_level.clamp(0.0, 1.0) * HISS_GAIN;
+
+        // クラックル: ランダムに発生して素早く減衰するクリック
+        if self.next_random().abs() < CRACKLE_RATE / sample_rate {
+            self.crackle_env = self.next_random().abs();
+        }
+        let click = self.crackle_env * self.next_random();
+        self.crackle_env *= (-1.0 / (CRACKLE_DECAY_SEC * sample_rate)).exp();
+        out += click * crackle_level.clamp(0.0, 1.0) * CRACKLE_GAIN;
+
+        // モーター音: 速度に比例した高さの唸り（倍音を少し足す）
+        let speed = speed.clamp(0.0, 1.0);
+        self.motor_phase += MOTOR_HZ * speed / sample_rate as f64;
+        self.motor_phase -= self.motor_phase.floor();
+        let whine = (TAU * self.motor_phase).sin() + 0.3 * (2.0 * TAU * self.motor_phase).sin();
+        out += whine as f32 * speed as f32 * motor_level.clamp(0.0, 1.0) * MOTOR_GAIN;
+
+        out
+    }
+
+    fn initial_state(seed: u32) -> u32 {
+        // xorshift は 0 から抜け出せないので避ける
+        if seed == 0 { 0x9E37_79B9 } else { seed }
+    }
+
+    /**
+     * xorshift32 で -1.0 -> 1.0 の乱数を生成
+     */
+    fn next_random(&mut self) -> f32 {
+        let mut x = self.rng;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.rng = x;
+        (x as f64 / u32::MAX as f64 * 2.0 - 1.0) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(seed: u32, samples: usize) -> Vec<f32> {
+        let mut noise = NoiseGenerator::new(seed);
+        (0..samples)
+            .map(|i| noise.process(1.0 - i as f64 / samples as f64, 1.0, 1.0, 1.0, 48_000.0))
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_is_deterministic() {
+        assert_eq!(render(99, 48_000), render(99, 48_000));
+        assert_ne!(render(99, 48_000), render(100, 48_000));
+    }
+
+    #[test]
+    fn reset_replays_the_same_noise() {
+        let mut noise = NoiseGenerator::new(3);
+        let first: Vec<f32> = (0..4_800).map(|_| noise.process(0.5, 1.0, 1.0, 1.0, 48_000.0)).collect();
+        noise.reset();
+        let second: Vec<f32> = (0..4_800).map(|_| noise.process(0.5, 1.0, 1.0, 1.0, 48_000.0)).collect();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn zero_levels_are_silent_and_full_levels_stay_bounded() {
+        let mut noise = NoiseGenerator::new(5);
+        for _ in 0..48_000 {
+            assert_eq!(noise.process(1.0, 0.0, 0.0, 0.0, 48_000.0), 0.0);
+        }
+
+        let peak = render(5, 96_000).iter().fold(0.0f32, |acc, x| acc.max(x.abs()));
+        assert!(peak > 0.0);
+        assert!(peak <= HISS_GAIN * 2.0 + CRACKLE_GAIN + MOTOR_GAIN * 1.3);
+    }
+}
@@ -0,0 +1,369 @@
+use std::f64::consts::PI;
+
+use super::engine::{EngineParams, TapeState, TapeStopEngine};
+use super::sample::Sample;
+
+// ハーフバンドフィルタの中心タップ（タップ数 = 2 * HALF_BAND_CENTER + 1）
+// 各段の遅延が整数サンプルになるよう 4 の倍数にしておく
+const HALF_BAND_CENTER: usize = 16;
+// 0 でないタップのうち、中心以外（中心から奇数番目）の数
+const HALF_BAND_TAPS: usize = HALF_BAND_CENTER;
+// 偶数側（遅延だけの枝）の遅延サンプル数
+const EVEN_DELAY: usize = HALF_BAND_CENTER / 2;
+// 対応する最大倍率
+pub const MAX_OVERSAMPLING: usize = 8;
+// 内部のサンプルレートの上限（高いレートで倍率を上げても遅延バッファが際限なく大きくならないように）
+pub const MAX_INTERNAL_SAMPLE_RATE: f32 = 384_000.0;
+
+/**
+ * ハーフバンドフィルタの奇数タップ h[2m + 1] を設計（ブラックマン窓付き sinc）
+ * 偶数タップは中心 (0.5) 以外すべて 0 になるので持たない
+ */
+fn half_band_taps<S: Sample>() -> [S; HALF_BAND_TAPS] {
+    let length = (2 * HALF_BAND_CENTER) as f64;
+    let mut taps = [0.0f64; HALF_BAND_TAPS];
+    for (m, tap) in taps.iter_mut().enumerate() {
+        let k = (2 * m + 1) as f64;
+        let x = (k - HALF_BAND_CENTER as f64) / 2.0;
+        let sinc = (PI * x).sin() / (PI * x);
+        let window = 0.42 - 0.5 * (2.0 * PI * k / length).cos() + 0.08 * (4.0 * PI * k / length).cos();
+        *tap = 0.5 * sinc * window;
+    }
+
+    // 直流ゲインが 1 になるよう、奇数タップの合計を 0.5 に揃える
+    let sum: f64 = taps.iter().sum();
+    let mut out = [S::ZERO; HALF_BAND_TAPS];
+    for (o, t) in out.iter_mut().zip(taps.iter()) {
+        *o = S::from_f64(t * 0.5 / sum);
+    }
+    out
+}
+
+/**
+ * 2倍アップサンプラー（ポリフェーズ）
+ * 偶数フェーズは遅延だけ、奇数フェーズは奇数タップの FIR で補間する
+ */
+#[derive(Clone)]
+struct HalfBandUp<S: Sample> {
+    history: [S; HALF_BAND_TAPS], // 入力の履歴（[0] が最新）
+}
+
+impl<S: Sample> HalfBandUp<S> {
+    fn new() -> Self {
+        Self { history: [S::ZERO; HALF_BAND_TAPS] }
+    }
+
+    #[inline]
+    fn process(&mut self, taps: &[S; HALF_BAND_TAPS], input: S) -> [S; 2] {
+        self.history.copy_within(0..HALF_BAND_TAPS - 1, 1);
+        self.history[0] = input;
+
+        // ゼロ挿入で半分になった振幅を 2 倍して戻す（中心タップ 0.5 × 2 = 1）
+        let even = self.history[EVEN_DELAY];
+        let mut odd = S::ZERO;
+        for (&t, &x) in taps.iter().zip(self.history.iter()) {
+            odd += t * x;
+        }
+        [even, S::from_f32(2.0) * odd]
+    }
+
+    fn reset(&mut self) {
+        self.history.fill(S::ZERO);
+    }
+}
+
+/**
+ * 2倍ダウンサンプラー（ポリフェーズ）
+ * 2サンプル組を受け取り、ローパスしてから1サンプルに間引く
+ */
+#[derive(Clone)]
+struct HalfBandDown<S: Sample> {
+    even: [S; EVEN_DELAY + 1], // 偶数サンプルの履歴（[0] が最新）
+    odd: [S; HALF_BAND_TAPS],  // 1つ前までの奇数サンプルの履歴（[0] が最新）
+}
+
+impl<S: Sample> HalfBandDown<S> {
+    fn new() -> Self {
+        Self { even: [S::ZERO; EVEN_DELAY + 1], odd: [S::ZERO; HALF_BAND_TAPS] }
+    }
+
+    #[inline]
+    fn process(&mut self, taps: &[S; HALF_BAND_TAPS], pair: [S; 2]) -> S {
+        self.even.copy_within(0..EVEN_DELAY, 1);
+        self.even[0] = pair[0];
+
+        let mut odd = S::ZERO;
+        for (&t, &x) in taps.iter().zip(self.odd.iter()) {
+            odd += t * x;
+        }
+        let out = S::from_f32(0.5) * self.even[EVEN_DELAY] + odd;
+
+        self.odd.copy_within(0..HALF_BAND_TAPS - 1, 1);
+        self.odd[0] = pair[1];
+        out
+    }
+
+    fn reset(&mut self) {
+        self.even.fill(S::ZERO);
+        self.odd.fill(S::ZERO);
+    }
+}
+
+/**
+ * TapeStopEngine をオーバーサンプリングで包むラッパー
+ * ハーフバンドフィルタを 2 倍ずつ重ねて 2x / 4x / 8x にする
+ * 倍率 1 のときはエンジンをそのまま呼ぶ
+ */
+pub struct OversampledEngine<S: Sample = f32> {
+    engine: TapeStopEngine<S>,   // 倍率分高いサンプルレートで動くエンジン
+    factor: usize,               // オーバーサンプリング倍率
+    taps: [S; HALF_BAND_TAPS],   // ハーフバンドフィルタの係数
+    ups: Vec<Vec<HalfBandUp<S>>>,     // チャンネル × 段ごとのアップサンプラー
+    downs: Vec<Vec<HalfBandDown<S>>>, // チャンネル × 段ごとのダウンサンプラー
+    upsampled: Vec<[S; MAX_OVERSAMPLING]>,   // チャンネルごとのアップサンプル結果
+    downsampled: Vec<[S; MAX_OVERSAMPLING]>, // チャンネルごとのエンジン出力
+    frame_in: Vec<S>,  // エンジンへ渡す1フレーム
+    frame_out: Vec<S>, // エンジンから受け取る1フレーム
+}
+
+impl<S: Sample> OversampledEngine<S> {
+    /**
+     * - factor オーバーサンプリング倍率（1, 2, 4, 8 のいずれか）
+     */
+    pub fn new(sample_rate: f32, max_seconds: f32, channels: usize, factor: usize) -> Self {
+        assert!(
+            factor.is_power_of_two() && factor <= MAX_OVERSAMPLING,
+            "oversampling factor must be 1, 2, 4 or 8"
+        );
+        let stages = factor.trailing_zeros() as usize;
+
+        Self {
+            engine: TapeStopEngine::new(sample_rate * factor as f32, max_seconds, channels),
+            factor,
+            taps: half_band_taps(),
+            ups: (0..channels).map(|_| vec![HalfBandUp::new(); stages]).collect(),
+            downs: (0..channels).map(|_| vec![HalfBandDown::new(); stages]).collect(),
+            upsampled: vec![[S::ZERO; MAX_OVERSAMPLING]; channels],
+            downsampled: vec![[S::ZERO; MAX_OVERSAMPLING]; channels],
+            frame_in: vec![S::ZERO; channels],
+            frame_out: vec![S::ZERO; channels],
+        }
+    }
+
+    /**
+     * サンプルレートとバッファの長さを変更する（リアルタイムスレッドから呼ばないこと）
+     * 内部のエンジンは倍率分高いサンプルレートで準備し直す
+     */
+    pub fn prepare(&mut self, sample_rate: f32, max_seconds: f32) {
+        self.engine.prepare(sample_rate * self.factor as f32, max_seconds);
+        self.reset();
+    }
+
+    /**
+     * サンプルレートだけを変更する（prepare と同じく非リアルタイム）
+     */
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.engine.set_sample_rate(sample_rate * self.factor as f32);
+        self.reset();
+    }
+
+    /**
+     * バッファの長さだけを変更する（prepare と同じく非リアルタイム）
+     */
+    pub fn set_max_seconds(&mut self, max_seconds: f32) {
+        self.engine.set_max_seconds(max_seconds);
+        self.reset();
+    }
+
+    /**
+     * 処理するチャンネル数を取得
+     */
+    pub fn channels(&self) -> usize {
+        self.engine.channels()
+    }
+
+    /**
+     * オーバーサンプリング倍率を取得
+     */
+    pub fn factor(&self) -> usize {
+        self.factor
+    }
+
+    /**
+     * フィルタによる遅延（元のサンプルレートでのサンプル数）を取得
+     */
+    pub fn latency(&self) -> u32 {
+        Self::latency_for(self.factor)
+    }
+
+    /**
+     * 倍率 factor で作ったときの遅延（エンジンを作らずに求める）
+     * 各段で、その段の低い側のレートで EVEN_DELAY × 2 サンプル遅れる
+     */
+    pub fn latency_for(factor: usize) -> u32 {
+        let stages = factor.trailing_zeros();
+        (0..stages).map(|s| (2 * EVEN_DELAY as u32) >> s).sum()
+    }
+
+    /**
+     * sample_rate で実際に使う倍率
+     * factor 以下で、内部のサンプルレートが MAX_INTERNAL_SAMPLE_RATE を超えない最大の倍率（192 kHz なら 2x まで）
+     */
+    pub fn supported_factor(sample_rate: f32, factor: usize) -> usize {
+        let mut factor = factor.clamp(1, MAX_OVERSAMPLING);
+        while factor > 1 && sample_rate * factor as f32 > MAX_INTERNAL_SAMPLE_RATE {
+            factor /= 2;
+        }
+        factor
+    }
+
+    /**
+     * エンジンとフィルタの状態をリセット
+     */
+    pub fn reset(&mut self) {
+        self.engine.reset();
+        for filter in self.ups.iter_mut().flatten() {
+            filter.reset();
+        }
+        for filter in self.downs.iter_mut().flatten() {
+            filter.reset();
+        }
+    }
+
+    /**
+     * 現在のテープの状態を取得
+     */
+    pub fn state(&self) -> TapeState {
+        self.engine.state()
+    }
+
+    /**
+     * 停止時間をバッファに収まるよう切り詰めているかどうか
+     */
+    pub fn is_clamped(&self) -> bool {
+        self.engine.is_clamped()
+    }
+
+    /**
+     * 通常再生に戻るまでに必要なサンプル数（元のサンプルレート、フィルタ遅延込み）を取得
+     */
+    pub fn tail_samples(&self) -> u32 {
+        let tail = self.engine.tail_samples();
+        if self.factor == 1 {
+            return tail;
+        }
+        tail.div_ceil(self.factor as u32) + self.latency()
+    }
+
+    /**
+     * 1フレーム分を処理
+     * パラメータはオーバーサンプル中の全サンプルで共通
+     */
+    pub fn process(&mut self, input: &[S], output: &mut [S], params: &EngineParams) {
+        if self.factor == 1 {
+            self.engine.process(input, output, params);
+            return;
+        }
+
+        // アップサンプリング（低い段から順に 2 倍ずつ）
+        for (ch, &x) in input.iter().enumerate() {
+            let buf = &mut self.upsampled[ch];
+            buf[0] = x;
+            let mut len = 1;
+            for up in &mut self.ups[ch] {
+                let mut next = [S::ZERO; MAX_OVERSAMPLING];
+                for i in 0..len {
+                    let [a, b] = up.process(&self.taps, buf[i]);
+                    next[2 * i] = a;
+                    next[2 * i + 1] = b;
+                }
+                len *= 2;
+                buf[..len].copy_from_slice(&next[..len]);
+            }
+        }
+
+        // 高いサンプルレートでエンジンを回す
+        for k in 0..self.factor {
+            for (ch, frame) in self.frame_in.iter_mut().enumerate() {
+                *frame = self.upsampled[ch][k];
+            }
+            self.engine.process(&self.frame_in, &mut self.frame_out, params);
+            for (ch, &frame) in self.frame_out.iter().enumerate() {
+                self.downsampled[ch][k] = frame;
+            }
+        }
+
+        // ダウンサンプリング（高い段から順に 1/2 ずつ）
+        for (ch, out) in output.iter_mut().enumerate() {
+            let buf = &mut self.downsampled[ch];
+            let mut len = self.factor;
+            for down in self.downs[ch].iter_mut().rev() {
+                len /= 2;
+                for i in 0..len {
+                    buf[i] = down.process(&self.taps, [buf[2 * i], buf[2 * i + 1]]);
+                }
+            }
+            *out = buf[0];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48_000.0;
+
+    #[test]
+    fn latency_is_whole_samples_per_factor() {
+        let latencies: Vec<u32> = [1, 2, 4, 8]
+            .iter()
+            .map(|&f| OversampledEngine::<f32>::new(SAMPLE_RATE, 0.1, 1, f).latency())
+            .collect();
+        assert_eq!(latencies, vec![0, 16, 24, 28]);
+
+        // エンジンを作らずに求めた遅延も同じ
+        let predicted: Vec<u32> = [1, 2, 4, 8].iter().map(|&f| OversampledEngine::<f32>::latency_for(f)).collect();
+        assert_eq!(predicted, latencies);
+    }
+
+    #[test]
+    fn factor_is_limited_by_the_internal_rate() {
+        let factors: Vec<usize> = [44_100.0, 48_000.0, 96_000.0, 192_000.0, 384_000.0]
+            .iter()
+            .map(|&rate| OversampledEngine::<f32>::supported_factor(rate, MAX_OVERSAMPLING))
+            .collect();
+        assert_eq!(factors, vec![8, 8, 4, 2, 1]);
+        assert_eq!(OversampledEngine::<f32>::supported_factor(48_000.0, 2), 2);
+    }
+
+    #[test]
+    fn oversampling_is_transparent_when_idle() {
+        // エフェクトが何も掛かっていない状態では、遅延を除いて入力と一致するはず
+        let params = EngineParams::default();
+        for factor in [2, 4, 8] {
+            let mut engine = OversampledEngine::new(SAMPLE_RATE, 0.1, 2, factor);
+            let latency = engine.latency() as usize;
+
+            let signal = |n: usize| {
+                let t = n as f32 / SAMPLE_RATE;
+                let l = (2.0 * std::f32::consts::PI * 440.0 * t).sin() * 0.5;
+                let r = (2.0 * std::f32::consts::PI * 3_000.0 * t).sin() * 0.3;
+                [l, r]
+            };
+
+            let mut out = [0.0f32; 2];
+            let mut max_error = 0.0f32;
+            for n in 0..2_000 {
+                engine.process(&signal(n), &mut out, &params);
+                if n >= 200 {
+                    let expected = signal(n - latency);
+                    for ch in 0..2 {
+                        max_error = max_error.max((out[ch] - expected[ch]).abs());
+                    }
+                }
+            }
+            assert!(max_error < 1.0e-3, "factor {factor}: max error {max_error}");
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,232 @@
+use std::fmt::Debug;
+use std::ops::{Add, AddAssign, Div, Mul, MulAssign, Neg, Sub};
+
+#[cfg(feature = "simd")]
+use wide::{f32x4, f64x4};
+
+// SIMD で同時に処理するレーン数
+pub const LANES: usize = 4;
+
+/**
+ * SIMD でまとめて計算する LANES 個分のサンプル
+ */
+pub trait SampleLanes<S>: Copy + Add<Output = Self> + Sub<Output = Self> + Mul<Output = Self> {
+    fn splat(value: S) -> Self;
+    fn from_array(values: [S; LANES]) -> Self;
+    fn to_array(self) -> [S; LANES];
+}
+
+#[cfg(feature = "simd")]
+impl SampleLanes<f32> for f32x4 {
+    #[inline]
+    fn splat(value: f32) -> Self {
+        f32x4::new([value; LANES])
+    }
+    #[inline]
+    fn from_array(values: [f32; LANES]) -> Self {
+        f32x4::new(values)
+    }
+    #[inline]
+    fn to_array(self) -> [f32; LANES] {
+        f32x4::to_array(self)
+    }
+}
+
+#[cfg(feature = "simd")]
+impl SampleLanes<f64> for f64x4 {
+    #[inline]
+    fn splat(value: f64) -> Self {
+        f64x4::new([value; LANES])
+    }
+    #[inline]
+    fn from_array(values: [f64; LANES]) -> Self {
+        f64x4::new(values)
+    }
+    #[inline]
+    fn to_array(self) -> [f64; LANES] {
+        f64x4::to_array(self)
+    }
+}
+
+/**
+ * simd フィーチャーを外したときの LANES 個分のサンプル（要素ごとにスカラーで計算する）
+ */
+#[cfg(not(feature = "simd"))]
+#[derive(Clone, Copy, Debug)]
+pub struct ScalarLanes<S>([S; LANES]);
+
+#[cfg(not(feature = "simd"))]
+impl<S: Copy + Add<Output = S>> Add for ScalarLanes<S> {
+    type Output = Self;
+    #[inline]
+    fn add(self, rhs: Self) -> Self {
+        Self(std::array::from_fn(|i| self.0[i] + rhs.0[i]))
+    }
+}
+
+#[cfg(not(feature = "simd"))]
+impl<S: Copy + Sub<Output = S>> Sub for ScalarLanes<S> {
+    type Output = Self;
+    #[inline]
+    fn sub(self, rhs: Self) -> Self {
+        Self(std::array::from_fn(|i| self.0[i] - rhs.0[i]))
+    }
+}
+
+#[cfg(not(feature = "simd"))]
+impl<S: Copy + Mul<Output = S>> Mul for ScalarLanes<S> {
+    type Output = Self;
+    #[inline]
+    fn mul(self, rhs: Self) -> Self {
+        Self(std::array::from_fn(|i| self.0[i] * rhs.0[i]))
+    }
+}
+
+#[cfg(not(feature = "simd"))]
+impl<S: Copy + Add<Output = S> + Sub<Output = S> + Mul<Output = S>> SampleLanes<S> for ScalarLanes<S> {
+    #[inline]
+    fn splat(value: S) -> Self {
+        Self([value; LANES])
+    }
+    #[inline]
+    fn from_array(values: [S; LANES]) -> Self {
+        Self(values)
+    }
+    #[inline]
+    fn to_array(self) -> [S; LANES] {
+        self.0
+    }
+}
+
+/**
+ * DSP で扱うサンプルの型（f32 / f64）
+ * バッファ、補間、フィルタ、クロスフェードなど音声が通る経路はこの型のまま計算する
+ */
+pub trait Sample:
+    Copy
+    + Default
+    + Debug
+    + PartialOrd
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+    + AddAssign
+    + MulAssign
+    + Send
+    + Sync
+    + 'static
+{
+    type Lanes: SampleLanes<Self>;
+
+    const ZERO: Self;
+    const ONE: Self;
+
+    fn from_f32(value: f32) -> Self;
+    fn from_f64(value: f64) -> Self;
+    fn to_f32(self) -> f32;
+    fn to_f64(self) -> f64;
+
+    fn abs(self) -> Self;
+    fn exp(self) -> Self;
+    fn tanh(self) -> Self;
+    fn copysign(self, sign: Self) -> Self;
+    fn clamp(self, min: Self, max: Self) -> Self;
+}
+
+impl Sample for f32 {
+    #[cfg(feature = "simd")]
+    type Lanes = f32x4;
+    #[cfg(not(feature = "simd"))]
+    type Lanes = ScalarLanes<f32>;
+
+    const ZERO: Self = 0.0;
+    const ONE: Self = 1.0;
+
+    #[inline]
+    fn from_f32(value: f32) -> Self {
+        value
+    }
+    #[inline]
+    fn from_f64(value: f64) -> Self {
+        value as f32
+    }
+    #[inline]
+    fn to_f32(self) -> f32 {
+        self
+    }
+    #[inline]
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+
+    #[inline]
+    fn abs(self) -> Self {
+        f32::abs(self)
+    }
+    #[inline]
+    fn exp(self) -> Self {
+        f32::exp(self)
+    }
+    #[inline]
+    fn tanh(self) -> Self {
+        f32::tanh(self)
+    }
+    #[inline]
+    fn copysign(self, sign: Self) -> Self {
+        f32::copysign(self, sign)
+    }
+    #[inline]
+    fn clamp(self, min: Self, max: Self) -> Self {
+        f32::clamp(self, min, max)
+    }
+}
+
+impl Sample for f64 {
+    #[cfg(feature = "simd")]
+    type Lanes = f64x4;
+    #[cfg(not(feature = "simd"))]
+    type Lanes = ScalarLanes<f64>;
+
+    const ZERO: Self = 0.0;
+    const ONE: Self = 1.0;
+
+    #[inline]
+    fn from_f32(value: f32) -> Self {
+        value as f64
+    }
+    #[inline]
+    fn from_f64(value: f64) -> Self {
+        value
+    }
+    #[inline]
+    fn to_f32(self) -> f32 {
+        self as f32
+    }
+    #[inline]
+    fn to_f64(self) -> f64 {
+        self
+    }
+
+    #[inline]
+    fn abs(self) -> Self {
+        f64::abs(self)
+    }
+    #[inline]
+    fn exp(self) -> Self {
+        f64::exp(self)
+    }
+    #[inline]
+    fn tanh(self) -> Self {
+        f64::tanh(self)
+    }
+    #[inline]
+    fn copysign(self, sign: Self) -> Self {
+        f64::copysign(self, sign)
+    }
+    #[inline]
+    fn clamp(self, min: Self, max: Self) -> Self {
+        f64::clamp(self, min, max)
+    }
+}
\ No newline at end of file
@@ -0,0 +1,102 @@
+// プリエンファシスの強さ（高域をどれだけ持ち上げてから歪ませるか）
+const EMPHASIS: f32 = 0.6;
+// drive 1.0 のときの入力ゲイン
+const MAX_DRIVE_GAIN: f32 = 10.0;
+// bias 1.0 のときの動作点のずれ
+const MAX_BIAS: f32 = 0.5;
+
+#[derive(Clone, Copy)]
+pub struct TapeSaturator {
+    prev_input: f32,  // プリエンファシス用の1サンプル前の入力
+    prev_output: f32, // ディエンファシス用の1サンプル前の出力
+}
+
+impl Default for TapeSaturator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TapeSaturator {
+    pub fn new() -> Self {
+        Self {
+            prev_input: 0.0,
+            prev_output: 0.0,
+        }
+    }
+
+    /**
+     * 非対称ウェーブシェイパーでテープの飽和を再現する
+     * - input 入力サンプル
+     * - drive 歪みの強さ (0.0 -> 1.0)
+     * - bias 非対称さ (-1.0 -> 1.0)
+     * - mix 原音とのミックス (0.0 -> 1.0)
+     */
+    #[inline]
+    pub fn process(&mut self, input: f32, drive: f32, bias: f32, mix: f32) -> f32 {
+        // プリエンファシス: H(z) = 1 + k(1 - z^-1)
+        let emphasized = input + EMPHASIS * (input - self.prev_input);
+        self.prev_input = input;
+
+        // 非対称な tanh。無入力時に直流が出ないよう、バイアス分を差し引く
+        // drive 0.0 で小信号ゲインが 1 になるよう正規化し、drive を上げた分は sqrt で持ち上げる
+        let gain = 1.0 + drive.clamp(0.0, 1.0) * (MAX_DRIVE_GAIN - 1.0);
+        let offset = bias.clamp(-1.0, 1.0) * MAX_BIAS;
+        let slope = 1.0 - offset.tanh().powi(2);
+        let shaped = ((gain * emphasized + offset).tanh() - offset.tanh()) / (slope * gain.sqrt());
+
+        // ディエンファシス: プリエンファシスの逆フィルター
+        let deemphasized = (shaped + EMPHASIS * self.prev_output) / (1.0 + EMPHASIS);
+        self.prev_output = deemphasized;
+
+        let mix = mix.clamp(0.0, 1.0);
+        input * (1.0 - mix) + deemphasized * mix
+    }
+
+    /**
+     * 状態をリセット
+     */
+    pub fn reset(&mut self) {
+        self.prev_input = 0.0;
+        self.prev_output = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(i: usize, amplitude: f32) -> f32 {
+        (i as f32 * 0.05).sin() * amplitude
+    }
+
+    #[test]
+    fn zero_mix_or_drive_leaves_the_signal_unchanged() {
+        let mut saturator = TapeSaturator::new();
+        for i in 0..10_000 {
+            let input = sine(i, 1.0);
+            assert_eq!(saturator.process(input, 1.0, 0.5, 0.0), input);
+        }
+
+        // drive 0 では小さな信号のゲインが 1 で、エンファシスも打ち消し合う
+        let mut saturator = TapeSaturator::new();
+        for i in 0..10_000 {
+            let input = sine(i, 0.01);
+            let out = saturator.process(input, 0.0, 0.0, 1.0);
+            assert!((out - input).abs() < 1.0e-4, "{i}: {input} -> {out}");
+        }
+    }
+
+    #[test]
+    fn output_stays_bounded() {
+        for bias in [-1.0, 0.0, 1.0] {
+            let mut saturator = TapeSaturator::new();
+            for i in 0..10_000 {
+                // 最大の drive でフルスケールの矩形波を入れる
+                let input = if (i / 50) % 2 == 0 { 1.0 } else { -1.0 };
+                let out = saturator.process(input, 1.0, bias, 1.0);
+                assert!(out.is_finite() && out.abs() <= 1.0, "bias {bias}: {input} -> {out}");
+            }
+        }
+    }
+}
\ No newline at end of file
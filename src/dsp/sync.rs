@@ -0,0 +1,71 @@
+#[cfg(feature = "plugin")]
+use nih_plug::prelude::Enum;
+
+// BPM同期用の拍数定義
+#[derive(PartialEq, Clone, Copy, Debug)]
+#[cfg_attr(feature = "plugin", derive(Enum))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(rename_all = "snake_case"))]
+pub enum SyncBeat {
+    #[cfg_attr(feature = "plugin", name = "1/8")]
+    Eight,
+    #[cfg_attr(feature = "plugin", name = "1/4")]
+    Quarter,
+    #[cfg_attr(feature = "plugin", name = "1/2")]
+    Half,
+    #[cfg_attr(feature = "plugin", name = "1 Bar")]
+    OneBar,
+    #[cfg_attr(feature = "plugin", name = "2 Bars")]
+    TwoBars,
+}
+
+impl SyncBeat {
+    // すべての拍数
+    pub const ALL: [SyncBeat; 5] = [
+        SyncBeat::Eight,
+        SyncBeat::Quarter,
+        SyncBeat::Half,
+        SyncBeat::OneBar,
+        SyncBeat::TwoBars,
+    ];
+
+    /**
+     * 同期の長さを拍数に変換
+     */
+    pub fn beats(self) -> f64 {
+        match self {
+            SyncBeat::Eight => 0.5,
+            SyncBeat::Quarter => 1.0,
+            SyncBeat::Half => 2.0,
+            SyncBeat::OneBar => 4.0,
+            SyncBeat::TwoBars => 8.0,
+        }
+    }
+}
+
+// ホールド時のグレインの長さ
+#[derive(PartialEq, Clone, Copy, Debug)]
+#[cfg_attr(feature = "plugin", derive(Enum))]
+pub enum HoldDivision {
+    #[cfg_attr(feature = "plugin", name = "1/32")]
+    ThirtySecond,
+    #[cfg_attr(feature = "plugin", name = "1/16")]
+    Sixteenth,
+    #[cfg_attr(feature = "plugin", name = "1/8")]
+    Eight,
+    #[cfg_attr(feature = "plugin", name = "1/4")]
+    Quarter,
+}
+
+impl HoldDivision {
+    /**
+     * グレインの長さを拍数に変換
+     */
+    pub fn beats(self) -> f64 {
+        match self {
+            HoldDivision::ThirtySecond => 0.125,
+            HoldDivision::Sixteenth => 0.25,
+            HoldDivision::Eight => 0.5,
+            HoldDivision::Quarter => 1.0,
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,14 @@
+use super::sample::Sample;
+
+/**
+ * ミッド/サイドでステレオ幅を変える
+ * - left, right 入力サンプル
+ * - width ステレオ幅 (0.0 = モノラル, 1.0 = そのまま)
+ */
+#[inline]
+pub fn mid_side<S: Sample>(left: S, right: S, width: f32) -> (S, S) {
+    let half = S::from_f32(0.5);
+    let mid = half * (left + right);
+    let side = half * (left - right) * S::from_f32(width.clamp(0.0, 1.0));
+    (mid + side, mid - side)
+}
\ No newline at end of file
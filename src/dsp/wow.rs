@@ -0,0 +1,150 @@
+use std::f64::consts::TAU;
+
+// 深さ 1.0 のときの最大速度偏差（±2%）
+const MAX_DEVIATION: f64 = 0.02;
+// 速度が 0 に近づいたときに深さを何倍まで増やすか（instability 1.0 のとき）
+const DEPTH_BOOST: f64 = 4.0;
+// 速度偏差の上限。これ以上にすると逆回転しうる
+const DEVIATION_LIMIT: f64 = 0.9;
+// ワウの周期に対するフラッターの速さ
+const FLUTTER_RATIO: f64 = 7.3;
+
+#[derive(Clone, Copy)]
+pub struct WowFlutter {
+    seed: u32,
+    rng: u32,
+    wow_phase: f64,      // ワウ LFO の位相 (0.0 -> 1.0)
+    flutter_phase: f64,  // 次の乱数目標までの位相 (0.0 -> 1.0)
+    flutter_target: f64, // フラッターの目標値 (-1.0 -> 1.0)
+    flutter_value: f64,  // 平滑化されたフラッター値
+    modulation: f64,     // ワウとフラッターを合成した現在の揺れ
+}
+
+impl WowFlutter {
+    pub fn new(seed: u32) -> Self {
+        Self {
+            seed,
+            rng: Self::initial_state(seed),
+            wow_phase: 0.0,
+            flutter_phase: 0.0,
+            flutter_target: 0.0,
+            flutter_value: 0.0,
+            modulation: 0.0,
+        }
+    }
+
+    /**
+     * 状態をリセット（同じシードから乱数を引き直す）
+     */
+    pub fn reset(&mut self) {
+        *self = Self::new(self.seed);
+    }
+
+    /**
+     * ヘッド速度に揺れを加える（advance と apply をまとめて行う）
+     * - speed カーブから求めた速度 (0.0 -> 1.0)
+     * - rate_hz ワウの周波数
+     * - depth 揺れの深さ (0.0 -> 1.0)
+     * - instability フラッターの乱雑さと、低速時の深さの増加量 (0.0 -> 1.0)
+     */
+    pub fn process(&mut self, speed: f64, rate_hz: f32, depth: f32, instability: f32, sample_rate: f32) -> f64 {
+        self.advance(rate_hz, instability, sample_rate);
+        self.apply(speed, depth, instability)
+    }
+
+    /**
+     * LFO と乱数を1サンプル進める
+     */
+    pub fn advance(&mut self, rate_hz: f32, instability: f32, sample_rate: f32) {
+        let sample_rate = sample_rate as f64;
+        let rate_hz = rate_hz.max(0.0) as f64;
+        let instability = instability.clamp(0.0, 1.0) as f64;
+
+        // ワウ: ゆっくりした正弦波
+        self.wow_phase += rate_hz / sample_rate;
+        self.wow_phase -= self.wow_phase.floor();
+        let wow = (TAU * self.wow_phase).sin();
+
+        // フラッター: 速めの乱数を平滑化したもの
+        let flutter_hz = rate_hz * FLUTTER_RATIO;
+        self.flutter_phase += flutter_hz / sample_rate;
+        if self.flutter_phase >= 1.0 {
+            self.flutter_phase -= self.flutter_phase.floor();
+            self.flutter_target = self.next_random();
+        }
+        let smoothing = 1.0 - (-TAU * flutter_hz / sample_rate).exp();
+        self.flutter_value += (self.flutter_target - self.flutter_value) * smoothing;
+
+        self.modulation = wow * (1.0 - 0.5 * instability) + self.flutter_value * instability;
+    }
+
+    /**
+     * 現在の揺れを速度に適用する
+     * 同じ揺れを複数のヘッドで共有できるよう、状態は進めない
+     */
+    pub fn apply(&self, speed: f64, depth: f32, instability: f32) -> f64 {
+        let depth = depth.clamp(0.0, 1.0) as f64;
+        let instability = instability.clamp(0.0, 1.0) as f64;
+
+        // 速度が落ちるほど揺れを深くする
+        let scaled_depth = depth * MAX_DEVIATION * (1.0 + DEPTH_BOOST * instability * (1.0 - speed.clamp(0.0, 1.0)));
+        let deviation = (self.modulation * scaled_depth).clamp(-DEVIATION_LIMIT, DEVIATION_LIMIT);
+
+        (speed * (1.0 + deviation)).max(0.0)
+    }
+
+    fn initial_state(seed: u32) -> u32 {
+        // xorshift は 0 から抜け出せないので避ける
+        if seed == 0 { 0x9E37_79B9 } else { seed }
+    }
+
+    /**
+     * xorshift32 で -1.0 -> 1.0 の乱数を生成
+     */
+    fn next_random(&mut self) -> f64 {
+        let mut x = self.rng;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.rng = x;
+        (x as f64 / u32::MAX as f64) * 2.0 - 1.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(seed: u32, speed: f64, samples: usize) -> Vec<f64> {
+        let mut wow = WowFlutter::new(seed);
+        (0..samples).map(|_| wow.process(speed, 3.0, 1.0, 1.0, 48_000.0)).collect()
+    }
+
+    #[test]
+    fn modulated_speed_never_goes_negative() {
+        for seed in [0, 1, 42, 0xDEAD_BEEF] {
+            let mut wow = WowFlutter::new(seed);
+            for i in 0..96_000 {
+                // 1.0 -> 0.0 まで減速しながら、極端な設定で揺らす
+                let speed = 1.0 - i as f64 / 96_000.0;
+                let out = wow.process(speed, 20.0, 1.0, 1.0, 48_000.0);
+                assert!(out >= 0.0, "seed {seed}: speed {speed} -> {out}");
+            }
+        }
+    }
+
+    #[test]
+    fn same_seed_is_deterministic() {
+        assert_eq!(render(1234, 0.3, 10_000), render(1234, 0.3, 10_000));
+        assert_ne!(render(1234, 0.3, 10_000), render(5678, 0.3, 10_000));
+    }
+
+    #[test]
+    fn reset_replays_the_same_modulation() {
+        let mut wow = WowFlutter::new(7);
+        let first: Vec<f64> = (0..5_000).map(|_| wow.process(0.5, 2.0, 0.8, 0.6, 44_100.0)).collect();
+        wow.reset();
+        let second: Vec<f64> = (0..5_000).map(|_| wow.process(0.5, 2.0, 0.8, 0.6, 44_100.0)).collect();
+        assert_eq!(first, second);
+    }
+}
@@ -4,7 +4,8 @@ use nih_plug_egui::egui::{Color32, Mesh, Vec2, Pos2, Shape, Stroke, Visuals};
 
 use std::sync::Arc;
 use std::sync::atomic::{AtomicU32, Ordering};
-use crate::params::{TapeStopParams, TapeCurve};
+use crate::dsp::curve::TapeCurve;
+use crate::params::TapeStopParams;
 
 fn get_pastel_color(t: f64, offset: f64) -> Color32 {
     let hue = ((t * 0.05 + offset) % 1.0) as f32;
@@ -195,6 +196,7 @@ pub fn create(
                                                         changed |= ui.selectable_value(&mut selected_curve, TapeCurve::Smooth,    "Smooth").clicked();
                                                         changed |= ui.selectable_value(&mut selected_curve, TapeCurve::SlowStart, "SlowStart").clicked();
                                                         changed |= ui.selectable_value(&mut selected_curve, TapeCurve::QuickCut,  "QuickCut").clicked();
+                                                        changed |= ui.selectable_value(&mut selected_curve, TapeCurve::PitchLinear, "PitchLinear").clicked();
                                                         changed
                                                     });
 
@@ -217,6 +219,17 @@ pub fn create(
                                             }
                                             ui.end_row();
 
+                                            // HOST TRANSPORT
+                                            ui.label(label("TRANSPORT"));
+                                            let mut follow_transport = params.follow_transport.value();
+                                            let transport_text = if follow_transport { "FOLLOW ▶" } else { "OFF" };
+                                            if ui.checkbox(&mut follow_transport, transport_text).changed() {
+                                                setter.begin_set_parameter(&params.follow_transport);
+                                                setter.set_parameter(&params.follow_transport, follow_transport);
+                                                setter.end_set_parameter(&params.follow_transport);
+                                            }
+                                            ui.end_row();
+
                                             // STOP TIME
                                             ui.label(label("STOP TIME"));
                                             ui.add(widgets::ParamSlider::for_param(&params.stop_time, setter).with_width(140.0));
@@ -226,6 +239,16 @@ pub fn create(
                                             ui.label(label("START TIME"));
                                             ui.add(widgets::ParamSlider::for_param(&params.start_time, setter).with_width(140.0));
                                             ui.end_row();
+
+                                            // MIX
+                                            ui.label(label("MIX"));
+                                            ui.add(widgets::ParamSlider::for_param(&params.mix, setter).with_width(140.0));
+                                            ui.end_row();
+
+                                            // OUTPUT
+                                            ui.label(label("OUTPUT"));
+                                            ui.add(widgets::ParamSlider::for_param(&params.output_gain, setter).with_width(140.0));
+                                            ui.end_row();
                                         });
 
                                     ui.add_space(25.0);
@@ -247,8 +270,15 @@ pub fn create(
                                     let response = ui.add(btn);
                                     let clicked = response.clicked()
                                         || (response.hovered() && ui.input(|i| i.pointer.any_released()));
+                                    // スタンドアロンではスペースキーでも切り替えられる
+                                    // DAWの中ではスペースキーをトランスポートに任せる
+                                    #[cfg(feature = "standalone")]
+                                    let space_pressed = setter.raw_context.plugin_api() == PluginApi::Standalone
+                                        && ui.input(|i| i.key_pressed(egui::Key::Space));
+                                    #[cfg(not(feature = "standalone"))]
+                                    let space_pressed = false;
 
-                                    if clicked {
+                                    if clicked || space_pressed {
                                         let new_val = !trigger_val;
                                         setter.begin_set_parameter(&params.trigger);
                                         setter.set_parameter(&params.trigger, new_val);
@@ -1,14 +1,20 @@
 ﻿use std::slice;
 
-use crate::dsp::engine::TapeStopEngine;
-use crate::params::{SyncBeat, TapeCurve};
+use crate::dsp::curve::TapeCurve;
+use crate::dsp::engine::{EngineParams, TapeStopEngine};
+use crate::dsp::oversampling::OversampledEngine;
+use crate::dsp::sample::Sample;
+use crate::dsp::sync::{HoldDivision, SyncBeat};
 
 #[repr(C)]
 #[derive(Clone, Copy)]
 pub struct K2sConfig {
+    pub struct_size: u32, // sizeof(K2sConfig)。古いヘッダでビルドした呼び出し側を見分ける
     pub sample_rate: f32,
     pub max_seconds: f32,
     pub channels: u32,
+    pub oversampling: u32, // 1, 2, 4, 8（0 はオーバーサンプリングなし）
+    pub double_precision: bool, // 内部処理を f64 で行う
 }
 
 #[repr(C)]
@@ -18,6 +24,7 @@ pub enum K2sCurve {
     Smooth = 1,
     SlowStart = 2,
     QuickCut = 3,
+    PitchLinear = 4,
 }
 
 #[repr(C)]
@@ -30,9 +37,23 @@ pub enum K2sSyncBeat {
     TwoBars = 4,
 }
 
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub enum K2sHoldDivision {
+    ThirtySecond = 0,
+    Sixteenth = 1,
+    Eight = 2,
+    Quarter = 3,
+}
+
+/**
+ * 1ブロックぶんの処理パラメータ
+ * 0 で初期化しただけ（K2sProcessParams{}）だと mix = 0 でドライのみ、output_gain = 0 で無音になるので、使う値はすべて設定すること
+ */
 #[repr(C)]
 #[derive(Clone, Copy)]
 pub struct K2sProcessParams {
+    pub struct_size: u32, // sizeof(K2sProcessParams)。古いヘッダでビルドした呼び出し側を見分ける
     pub trigger: bool,
     pub stop_time_sec: f32,
     pub start_time_sec: f32,
@@ -41,13 +62,193 @@ pub struct K2sProcessParams {
     pub sync_beat: K2sSyncBeat,
     pub bpm: f64,
     pub enable_filter: bool,
+    pub mix: f32,
+    pub output_gain: f32,
+    pub enable_limiter: bool,
+    pub enable_wow: bool,
+    pub wow_rate: f32,
+    pub wow_depth: f32,
+    pub wow_instability: f32,
+    pub enable_saturation: bool,
+    pub saturation_drive: f32,
+    pub saturation_bias: f32,
+    pub saturation_mix: f32,
+    pub saturation_link: bool,
+    pub enable_decimate: bool,
+    pub decimate_amount: f32,
+    pub enable_bitcrush: bool,
+    pub bitcrush_amount: f32,
+    pub lofi_anti_alias: bool,
+    pub stereo_spread: f32,
+    pub enable_width: bool,
+    pub width_at_stop: f32,
+    pub width_response: f32,
+    pub enable_noise: bool,
+    pub hiss_level: f32,
+    pub crackle_level: f32,
+    pub motor_level: f32,
+    pub enable_hold: bool,
+    pub hold_division: K2sHoldDivision,
+    pub hold_crossfade: f32,
+    pub hold_repeats: u32,
+    pub pitch_floor_st: f32,
+    pub land_on_beat: bool,
+}
+
+/**
+ * 内部のサンプル型ごとのエンジンと1フレーム分の作業バッファ
+ */
+struct FrameEngine<S: Sample> {
+    engine: OversampledEngine<S>,
+    frame_in: Vec<S>,
+    frame_out: Vec<S>,
+}
+
+impl<S: Sample> FrameEngine<S> {
+    fn new(config: &K2sConfig, factor: usize) -> Self {
+        let channels = config.channels as usize;
+        Self {
+            engine: OversampledEngine::new(config.sample_rate, config.max_seconds, channels, factor),
+            frame_in: vec![S::ZERO; channels],
+            frame_out: vec![S::ZERO; channels],
+        }
+    }
+
+    fn matches(&self, channels: usize, factor: usize) -> bool {
+        self.engine.channels() == channels && self.engine.factor() == factor
+    }
+
+    /**
+     * インターリーブされたバッファを処理（呼び出し側の型 T と内部の型 S が違う場合は変換する）
+     */
+    fn process_interleaved<T: Sample>(&mut self, input: &[T], output: &mut [T], params: &EngineParams) {
+        let channels = self.frame_in.len();
+        for (in_frame, out_frame) in input.chunks_exact(channels).zip(output.chunks_exact_mut(channels)) {
+            for (dst, &src) in self.frame_in.iter_mut().zip(in_frame.iter()) {
+                *dst = S::from_f64(src.to_f64());
+            }
+            self.engine.process(&self.frame_in, &mut self.frame_out, params);
+            for (dst, &src) in out_frame.iter_mut().zip(self.frame_out.iter()) {
+                *dst = T::from_f64(src.to_f64());
+            }
+        }
+    }
+}
+
+/**
+ * 呼び出し側の構造体をポインタ越しに読む（null やレイアウトが違う場合は None）
+ * 古いヘッダでビルドされた呼び出し側の構造体は短いので、先頭の struct_size を読んで合っていたときだけ残りを読む
+ * ptr が null でなければ、どの版のヘッダの構造体でも先頭の4バイトは読めることを呼び出し側が保証する
+ */
+fn read_versioned<T: Copy>(ptr: *const T) -> Option<T> {
+    if ptr.is_null() {
+        return None;
+    }
+
+    // SAFETY: Non-null, and caller guarantees the first four bytes of the struct are readable.
+    let struct_size = unsafe { ptr.cast::<u32>().read() };
+    if struct_size as usize != size_of::<T>() {
+        return None;
+    }
+
+    // SAFETY: The caller's struct has the same size, so it has the same layout as `T`.
+    Some(unsafe { ptr.read() })
+}
+
+/**
+ * 設定からオーバーサンプリング倍率を求める（不正な設定なら None）
+ * 内部のサンプルレートが上限を超える倍率は、そのサンプルレートで使える最大の倍率に下げる
+ */
+fn oversampling_factor(config: &K2sConfig) -> Option<usize> {
+    if config.sample_rate <= 0.0 || config.max_seconds <= 0.0 || config.channels == 0 {
+        return None;
+    }
+
+    match config.oversampling {
+        0 | 1 => Some(1),
+        2 | 4 | 8 => Some(OversampledEngine::<f32>::supported_factor(config.sample_rate, config.oversampling as usize)),
+        _ => None,
+    }
+}
+
+enum HandleEngine {
+    Single(FrameEngine<f32>),
+    Double(FrameEngine<f64>),
 }
 
 struct K2sHandle {
-    engine: TapeStopEngine,
+    engine: HandleEngine,
     channels: usize,
-    frame_in: Vec<f32>,
-    frame_out: Vec<f32>,
+}
+
+impl K2sHandle {
+    fn new(config: &K2sConfig) -> Option<Self> {
+        let factor = oversampling_factor(config)?;
+        let engine = if config.double_precision {
+            HandleEngine::Double(FrameEngine::new(config, factor))
+        } else {
+            HandleEngine::Single(FrameEngine::new(config, factor))
+        };
+
+        Some(Self {
+            engine,
+            channels: config.channels as usize,
+        })
+    }
+
+    /**
+     * 設定を反映する
+     * チャンネル数・倍率・精度が同じならバッファを使い回し、違う場合だけエンジンを作り直す
+     */
+    fn prepare(&mut self, config: &K2sConfig) -> bool {
+        let factor = match oversampling_factor(config) {
+            Some(factor) => factor,
+            None => return false,
+        };
+
+        let channels = config.channels as usize;
+        match &mut self.engine {
+            HandleEngine::Single(e) if !config.double_precision && e.matches(channels, factor) => {
+                e.engine.prepare(config.sample_rate, config.max_seconds);
+            }
+            HandleEngine::Double(e) if config.double_precision && e.matches(channels, factor) => {
+                e.engine.prepare(config.sample_rate, config.max_seconds);
+            }
+            _ => match Self::new(config) {
+                Some(handle) => *self = handle,
+                None => return false,
+            },
+        }
+        true
+    }
+
+    fn reset(&mut self) {
+        match &mut self.engine {
+            HandleEngine::Single(e) => e.engine.reset(),
+            HandleEngine::Double(e) => e.engine.reset(),
+        }
+    }
+
+    fn is_clamped(&self) -> bool {
+        match &self.engine {
+            HandleEngine::Single(e) => e.engine.is_clamped(),
+            HandleEngine::Double(e) => e.engine.is_clamped(),
+        }
+    }
+
+    fn latency(&self) -> u32 {
+        match &self.engine {
+            HandleEngine::Single(e) => e.engine.latency(),
+            HandleEngine::Double(e) => e.engine.latency(),
+        }
+    }
+
+    fn process_interleaved<T: Sample>(&mut self, input: &[T], output: &mut [T], params: &EngineParams) {
+        match &mut self.engine {
+            HandleEngine::Single(e) => e.process_interleaved(input, output, params),
+            HandleEngine::Double(e) => e.process_interleaved(input, output, params),
+        }
+    }
 }
 
 #[repr(C)]
@@ -62,6 +263,7 @@ impl K2sCurve {
             K2sCurve::Smooth => TapeCurve::Smooth,
             K2sCurve::SlowStart => TapeCurve::SlowStart,
             K2sCurve::QuickCut => TapeCurve::QuickCut,
+            K2sCurve::PitchLinear => TapeCurve::PitchLinear,
         }
     }
 }
@@ -78,22 +280,99 @@ impl K2sSyncBeat {
     }
 }
 
-#[unsafe(no_mangle)]
-pub extern "C" fn k2s_create(config: K2sConfig) -> *mut K2sOpaqueHandle {
-    if config.sample_rate <= 0.0 || config.max_seconds <= 0.0 || config.channels == 0 {
-        return core::ptr::null_mut();
+impl K2sHoldDivision {
+    fn to_internal(self) -> HoldDivision {
+        match self {
+            K2sHoldDivision::ThirtySecond => HoldDivision::ThirtySecond,
+            K2sHoldDivision::Sixteenth => HoldDivision::Sixteenth,
+            K2sHoldDivision::Eight => HoldDivision::Eight,
+            K2sHoldDivision::Quarter => HoldDivision::Quarter,
+        }
+    }
+}
+
+impl K2sProcessParams {
+    fn to_internal(self) -> EngineParams {
+        EngineParams {
+            trigger: self.trigger,
+            stop_time_sec: self.stop_time_sec,
+            start_time_sec: self.start_time_sec,
+            curve: self.curve.to_internal(),
+            use_sync: self.use_sync,
+            sync_beat: self.sync_beat.to_internal(),
+            bpm: self.bpm,
+            enable_filter: self.enable_filter,
+            mix: self.mix.clamp(0.0, 1.0),
+            output_gain: self.output_gain.max(0.0),
+            enable_limiter: self.enable_limiter,
+            enable_wow: self.enable_wow,
+            wow_rate: self.wow_rate,
+            wow_depth: self.wow_depth,
+            wow_instability: self.wow_instability,
+            enable_saturation: self.enable_saturation,
+            saturation_drive: self.saturation_drive,
+            saturation_bias: self.saturation_bias,
+            saturation_mix: self.saturation_mix,
+            saturation_link: self.saturation_link,
+            enable_decimate: self.enable_decimate,
+            decimate_amount: self.decimate_amount,
+            enable_bitcrush: self.enable_bitcrush,
+            bitcrush_amount: self.bitcrush_amount,
+            lofi_anti_alias: self.lofi_anti_alias,
+            stereo_spread: self.stereo_spread,
+            enable_width: self.enable_width,
+            width_at_stop: self.width_at_stop,
+            width_response: self.width_response,
+            enable_noise: self.enable_noise,
+            hiss_level: self.hiss_level,
+            crackle_level: self.crackle_level,
+            motor_level: self.motor_level,
+            enable_hold: self.enable_hold,
+            hold_division: self.hold_division.to_internal(),
+            hold_crossfade: self.hold_crossfade,
+            hold_repeats: self.hold_repeats,
+            pitch_floor_st: self.pitch_floor_st,
+            land_on_beat: self.land_on_beat,
+        }
     }
+}
 
-    let handle = K2sHandle {
-        engine: TapeStopEngine::new(config.sample_rate, config.max_seconds, config.channels as usize),
-        channels: config.channels as usize,
-        frame_in: vec![0.0_f32; config.channels as usize],
-        frame_out: vec![0.0_f32; config.channels as usize],
+/**
+ * 設定からハンドルを作る（設定が null・不正・別のヘッダの構造体なら null を返す）
+ */
+#[unsafe(no_mangle)]
+pub extern "C" fn k2s_create(config: *const K2sConfig) -> *mut K2sOpaqueHandle {
+    let config = match read_versioned(config) {
+        Some(config) => config,
+        None => return core::ptr::null_mut(),
+    };
+    let handle = match K2sHandle::new(&config) {
+        Some(handle) => handle,
+        None => return core::ptr::null_mut(),
     };
 
     Box::into_raw(Box::new(handle)) as *mut K2sOpaqueHandle
 }
 
+/**
+ * 既存のハンドルを新しい設定で準備し直す（リアルタイムスレッドから呼ばないこと）
+ * ハンドルはそのまま使い続けられる。設定が不正なときは何も変えずに false を返す
+ */
+#[unsafe(no_mangle)]
+pub extern "C" fn k2s_prepare(handle: *mut K2sOpaqueHandle, config: *const K2sConfig) -> bool {
+    if handle.is_null() {
+        return false;
+    }
+    let config = match read_versioned(config) {
+        Some(config) => config,
+        None => return false,
+    };
+
+    // SAFETY: Null has been checked, and caller guarantees a valid mutable handle.
+    let state = unsafe { &mut *(handle as *mut K2sHandle) };
+    state.prepare(&config)
+}
+
 #[unsafe(no_mangle)]
 pub extern "C" fn k2s_destroy(handle: *mut K2sOpaqueHandle) {
     if handle.is_null() {
@@ -114,26 +393,84 @@ pub extern "C" fn k2s_reset(handle: *mut K2sOpaqueHandle) -> bool {
 
     // SAFETY: Null has been checked, and caller guarantees a valid mutable handle.
     let state = unsafe { &mut *(handle as *mut K2sHandle) };
-    state.engine.reset();
+    state.reset();
     true
 }
 
+#[unsafe(no_mangle)]
+pub extern "C" fn k2s_get_latency(handle: *const K2sOpaqueHandle) -> u32 {
+    if handle.is_null() {
+        return 0;
+    }
+
+    // SAFETY: Null has been checked, and caller guarantees a valid handle.
+    let state = unsafe { &*(handle as *const K2sHandle) };
+    state.latency()
+}
+
+/**
+ * 指定した最低テンポまで、停止時間を切り詰めずに済む max_seconds を求める
+ * 遅れが MAX_LAG_SEC を超える組み合わせ（Land on Beat と深い PitchLinear など）は切り詰められる
+ */
+#[unsafe(no_mangle)]
+pub extern "C" fn k2s_required_seconds(min_bpm: f64) -> f32 {
+    if min_bpm <= 0.0 {
+        return 0.0;
+    }
+    TapeStopEngine::<f32>::required_seconds(min_bpm)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn k2s_is_clamped(handle: *const K2sOpaqueHandle) -> bool {
+    if handle.is_null() {
+        return false;
+    }
+
+    // SAFETY: Null has been checked, and caller guarantees a valid handle.
+    let state = unsafe { &*(handle as *const K2sHandle) };
+    state.is_clamped()
+}
+
 #[unsafe(no_mangle)]
 pub extern "C" fn k2s_process_interleaved_f32(
     handle: *mut K2sOpaqueHandle,
     input: *const f32,
     output: *mut f32,
     frames: usize,
-    params: K2sProcessParams,
+    params: *const K2sProcessParams,
+) -> bool {
+    process_interleaved(handle, input, output, frames, params)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn k2s_process_interleaved_f64(
+    handle: *mut K2sOpaqueHandle,
+    input: *const f64,
+    output: *mut f64,
+    frames: usize,
+    params: *const K2sProcessParams,
+) -> bool {
+    process_interleaved(handle, input, output, frames, params)
+}
+
+fn process_interleaved<T: Sample>(
+    handle: *mut K2sOpaqueHandle,
+    input: *const T,
+    output: *mut T,
+    frames: usize,
+    params: *const K2sProcessParams,
 ) -> bool {
     if handle.is_null() || input.is_null() || output.is_null() {
         return false;
     }
+    let params = match read_versioned(params) {
+        Some(params) => params,
+        None => return false,
+    };
 
     // SAFETY: Pointers are non-null, and lengths are derived from caller-provided frame count.
     let state = unsafe { &mut *(handle as *mut K2sHandle) };
-    let channels = state.channels;
-    let total_samples = match frames.checked_mul(channels) {
+    let total_samples = match frames.checked_mul(state.channels) {
         Some(v) => v,
         None => return false,
     };
@@ -143,29 +480,7 @@ pub extern "C" fn k2s_process_interleaved_f32(
     // SAFETY: Caller provides valid output buffer with at least `total_samples` elements.
     let out_buf = unsafe { slice::from_raw_parts_mut(output, total_samples) };
 
-    let curve = params.curve.to_internal();
-    let sync_beat = params.sync_beat.to_internal();
-
-    for frame in 0..frames {
-        let start = frame * channels;
-        let end = start + channels;
-
-        state.frame_in.copy_from_slice(&in_buf[start..end]);
-        state.engine.process(
-            &state.frame_in,
-            &mut state.frame_out,
-            params.trigger,
-            params.stop_time_sec,
-            params.start_time_sec,
-            curve,
-            params.use_sync,
-            sync_beat,
-            params.bpm,
-            params.enable_filter,
-        );
-        out_buf[start..end].copy_from_slice(&state.frame_out);
-    }
-
+    state.process_interleaved(in_buf, out_buf, &params.to_internal());
     true
 }
 
@@ -173,14 +488,62 @@ pub extern "C" fn k2s_process_interleaved_f32(
 mod tests {
     use super::*;
 
+    fn idle_params() -> K2sProcessParams {
+        K2sProcessParams {
+            struct_size: size_of::<K2sProcessParams>() as u32,
+            trigger: false,
+            stop_time_sec: 0.5,
+            start_time_sec: 0.5,
+            curve: K2sCurve::Linear,
+            use_sync: false,
+            sync_beat: K2sSyncBeat::Quarter,
+            bpm: 120.0,
+            enable_filter: true,
+            mix: 1.0,
+            output_gain: 1.0,
+            enable_limiter: false,
+            enable_wow: false,
+            wow_rate: 0.8,
+            wow_depth: 0.3,
+            wow_instability: 0.5,
+            enable_saturation: false,
+            saturation_drive: 0.3,
+            saturation_bias: 0.2,
+            saturation_mix: 1.0,
+            saturation_link: true,
+            enable_decimate: false,
+            decimate_amount: 0.5,
+            enable_bitcrush: false,
+            bitcrush_amount: 0.5,
+            lofi_anti_alias: true,
+            stereo_spread: 0.0,
+            enable_width: false,
+            width_at_stop: 0.0,
+            width_response: 1.0,
+            enable_noise: false,
+            hiss_level: 0.0,
+            crackle_level: 0.0,
+            motor_level: 0.0,
+            enable_hold: false,
+            hold_division: K2sHoldDivision::Sixteenth,
+            hold_crossfade: 0.1,
+            hold_repeats: 0,
+            pitch_floor_st: -36.0,
+            land_on_beat: false,
+        }
+    }
+
     #[test]
     fn ffi_matches_direct_engine_for_interleaved_stereo() {
         let frames = 64usize;
         let channels = 2usize;
         let cfg = K2sConfig {
+            struct_size: size_of::<K2sConfig>() as u32,
             sample_rate: 48_000.0,
             max_seconds: 3.0,
             channels: channels as u32,
+            oversampling: 1,
+            double_precision: false,
         };
 
         let mut input = vec![0.0f32; frames * channels];
@@ -191,6 +554,7 @@ mod tests {
         }
 
         let params = K2sProcessParams {
+            struct_size: size_of::<K2sProcessParams>() as u32,
             trigger: true,
             stop_time_sec: 0.5,
             start_time_sec: 0.5,
@@ -199,6 +563,37 @@ mod tests {
             sync_beat: K2sSyncBeat::Quarter,
             bpm: 120.0,
             enable_filter: true,
+            mix: 1.0,
+            output_gain: 1.0,
+            enable_limiter: true,
+            enable_wow: true,
+            wow_rate: 0.8,
+            wow_depth: 0.5,
+            wow_instability: 0.5,
+            enable_saturation: true,
+            saturation_drive: 0.3,
+            saturation_bias: 0.2,
+            saturation_mix: 1.0,
+            saturation_link: true,
+            enable_decimate: true,
+            decimate_amount: 0.5,
+            enable_bitcrush: true,
+            bitcrush_amount: 0.5,
+            lofi_anti_alias: true,
+            stereo_spread: 0.5,
+            enable_width: true,
+            width_at_stop: 0.0,
+            width_response: 1.0,
+            enable_noise: true,
+            hiss_level: 0.3,
+            crackle_level: 0.2,
+            motor_level: 0.2,
+            enable_hold: true,
+            hold_division: K2sHoldDivision::Sixteenth,
+            hold_crossfade: 0.1,
+            hold_repeats: 4,
+            pitch_floor_st: -36.0,
+            land_on_beat: false,
         };
 
         let mut expected = vec![0.0f32; frames * channels];
@@ -210,26 +605,15 @@ mod tests {
             let start = frame * channels;
             let end = start + channels;
             frame_in.copy_from_slice(&input[start..end]);
-            engine.process(
-                &frame_in,
-                &mut frame_out,
-                params.trigger,
-                params.stop_time_sec,
-                params.start_time_sec,
-                params.curve.to_internal(),
-                params.use_sync,
-                params.sync_beat.to_internal(),
-                params.bpm,
-                params.enable_filter,
-            );
+            engine.process(&frame_in, &mut frame_out, &params.to_internal());
             expected[start..end].copy_from_slice(&frame_out);
         }
 
         let mut actual = vec![0.0f32; frames * channels];
-        let handle = k2s_create(cfg);
+        let handle = k2s_create(&cfg);
         assert!(!handle.is_null());
 
-        let ok = k2s_process_interleaved_f32(handle, input.as_ptr(), actual.as_mut_ptr(), frames, params);
+        let ok = k2s_process_interleaved_f32(handle, input.as_ptr(), actual.as_mut_ptr(), frames, &params);
         assert!(ok);
 
         for (a, b) in actual.iter().zip(expected.iter()) {
@@ -238,5 +622,173 @@ mod tests {
 
         k2s_destroy(handle);
     }
-}
 
+    #[test]
+    fn ffi_reports_oversampling_latency() {
+        let mut cfg = K2sConfig {
+            struct_size: size_of::<K2sConfig>() as u32,
+            sample_rate: 48_000.0,
+            max_seconds: 1.0,
+            channels: 2,
+            oversampling: 0,
+            double_precision: false,
+        };
+
+        let handle = k2s_create(&cfg);
+        assert_eq!(k2s_get_latency(handle), 0);
+        k2s_destroy(handle);
+
+        cfg.oversampling = 4;
+        let handle = k2s_create(&cfg);
+        assert_eq!(k2s_get_latency(handle), 24);
+        k2s_destroy(handle);
+
+        cfg.oversampling = 3;
+        assert!(k2s_create(&cfg).is_null());
+    }
+
+    #[test]
+    fn f64_entry_point_matches_f32_within_precision() {
+        let frames = 2_000usize;
+        let channels = 2usize;
+        let mut cfg = K2sConfig {
+            struct_size: size_of::<K2sConfig>() as u32,
+            sample_rate: 48_000.0,
+            max_seconds: 1.0,
+            channels: channels as u32,
+            oversampling: 2,
+            double_precision: false,
+        };
+
+        let input: Vec<f64> = (0..frames * channels).map(|i| ((i / channels) as f64 * 0.02).sin() * 0.5).collect();
+        let input_f32: Vec<f32> = input.iter().map(|&x| x as f32).collect();
+
+        let params = K2sProcessParams {
+            trigger: true,
+            stop_time_sec: 0.02,
+            start_time_sec: 0.02,
+            ..idle_params()
+        };
+
+        let single = k2s_create(&cfg);
+        let mut out_single = vec![0.0f32; frames * channels];
+        assert!(k2s_process_interleaved_f32(single, input_f32.as_ptr(), out_single.as_mut_ptr(), frames, &params));
+        k2s_destroy(single);
+
+        cfg.double_precision = true;
+        let double = k2s_create(&cfg);
+        let mut out_double = vec![0.0f64; frames * channels];
+        assert!(k2s_process_interleaved_f64(double, input.as_ptr(), out_double.as_mut_ptr(), frames, &params));
+        k2s_destroy(double);
+
+        for (a, b) in out_single.iter().zip(out_double.iter()) {
+            assert!((*a as f64 - b).abs() < 1.0e-4);
+        }
+    }
+
+    #[test]
+    fn prepare_keeps_the_handle_usable_across_changes() {
+        let frames = 256usize;
+        let mut cfg = K2sConfig {
+            struct_size: size_of::<K2sConfig>() as u32,
+            sample_rate: 48_000.0,
+            max_seconds: 1.0,
+            channels: 2,
+            oversampling: 0,
+            double_precision: false,
+        };
+        let params = idle_params();
+
+        let input = vec![0.25f32; frames * 2];
+        let mut output = vec![0.0f32; frames * 2];
+        let handle = k2s_create(&cfg);
+        assert!(k2s_process_interleaved_f32(handle, input.as_ptr(), output.as_mut_ptr(), frames, &params));
+
+        // サンプルレートと長さだけ変える（バッファを使い回す）
+        cfg.sample_rate = 96_000.0;
+        cfg.max_seconds = 2.0;
+        assert!(k2s_prepare(handle, &cfg));
+        assert!(k2s_process_interleaved_f32(handle, input.as_ptr(), output.as_mut_ptr(), frames, &params));
+        assert!(output.iter().all(|&x| (x - 0.25).abs() < 1.0e-6));
+
+        // 倍率と精度を変える（エンジンを作り直す）
+        cfg.oversampling = 2;
+        cfg.double_precision = true;
+        assert!(k2s_prepare(handle, &cfg));
+        assert_eq!(k2s_get_latency(handle), 16);
+
+        // 不正な設定では何も変わらない
+        cfg.channels = 0;
+        assert!(!k2s_prepare(handle, &cfg));
+        assert_eq!(k2s_get_latency(handle), 16);
+        assert!(k2s_process_interleaved_f32(handle, input.as_ptr(), output.as_mut_ptr(), frames, &params));
+
+        k2s_destroy(handle);
+    }
+
+    /**
+     * struct_size を持たない古いヘッダの K2sConfig
+     */
+    #[repr(C)]
+    struct OldConfig {
+        sample_rate: f32,
+        max_seconds: f32,
+        channels: u32,
+        oversampling: u32,
+        double_precision: bool,
+    }
+
+    /**
+     * struct_size を持たない古いヘッダの K2sProcessParams の先頭（パディングは 0 で埋めておく）
+     */
+    #[repr(C)]
+    struct OldProcessParams {
+        trigger: bool,
+        padding: [u8; 3],
+        stop_time_sec: f32,
+        start_time_sec: f32,
+    }
+
+    #[test]
+    fn structs_from_another_header_are_rejected() {
+        let cfg = K2sConfig {
+            struct_size: size_of::<K2sConfig>() as u32,
+            sample_rate: 48_000.0,
+            max_seconds: 1.0,
+            channels: 1,
+            oversampling: 0,
+            double_precision: false,
+        };
+        let handle = k2s_create(&cfg);
+        assert!(!handle.is_null());
+
+        // 古いヘッダの構造体は struct_size の位置に別の値が入っていて、新しい構造体より短い
+        let old_cfg = OldConfig {
+            sample_rate: 48_000.0,
+            max_seconds: 1.0,
+            channels: 1,
+            oversampling: 0,
+            double_precision: false,
+        };
+        let old_cfg = &old_cfg as *const OldConfig as *const K2sConfig;
+        assert!(k2s_create(old_cfg).is_null());
+        assert!(!k2s_prepare(handle, old_cfg));
+        assert!(k2s_create(core::ptr::null()).is_null());
+        assert!(!k2s_prepare(handle, core::ptr::null()));
+
+        let input = [0.25f32; 16];
+        let mut output = [0.0f32; 16];
+        let old_params = OldProcessParams {
+            trigger: true,
+            padding: [0; 3],
+            stop_time_sec: 0.5,
+            start_time_sec: 0.5,
+        };
+        let old_params = &old_params as *const OldProcessParams as *const K2sProcessParams;
+        assert!(!k2s_process_interleaved_f32(handle, input.as_ptr(), output.as_mut_ptr(), 16, old_params));
+        assert!(!k2s_process_interleaved_f32(handle, input.as_ptr(), output.as_mut_ptr(), 16, core::ptr::null()));
+        assert!(k2s_process_interleaved_f32(handle, input.as_ptr(), output.as_mut_ptr(), 16, &idle_params()));
+
+        k2s_destroy(handle);
+    }
+}
\ No newline at end of file
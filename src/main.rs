@@ -0,0 +1,5 @@
+fn main() {
+    if !Kyun2Stop::standalone::run() {
+        std::process::exit(1);
+    }
+}
\ No newline at end of file
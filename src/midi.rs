@@ -0,0 +1,215 @@
+use std::fs;
+use std::path::Path;
+
+use midly::{MetaMessage, MidiMessage, Smf, Timing, TrackEventKind};
+
+use crate::render::{RenderError, TempoChange, Timeline, TimelineEvent};
+
+// SMFにテンポがないときのテンポ（マイクロ秒/拍、120 BPM）
+const DEFAULT_TEMPO_US: u32 = 500_000;
+
+/**
+ * SMFのどのノートをトリガーとして使うか
+ */
+#[derive(Clone, Debug, Default)]
+pub struct MidiTrigger {
+    pub channel: Option<u8>,  // MIDIチャンネル（0〜15）。None ならすべて
+    pub note: Option<u8>,     // ノート番号。None ならすべて
+    pub event: TimelineEvent, // 各イベントのカーブや時間（stop / start はノートの時刻で上書きする）
+}
+
+impl MidiTrigger {
+    fn matches(&self, channel: u8, key: u8) -> bool {
+        self.channel.is_none_or(|c| c == channel) && self.note.is_none_or(|n| n == key)
+    }
+}
+
+/**
+ * ティック数を秒に変換
+ * - tempo_us 1拍のマイクロ秒（タイムコード形式では使わない）
+ */
+fn ticks_to_seconds(ticks: u64, timing: Timing, tempo_us: u32) -> f64 {
+    match timing {
+        Timing::Metrical(ticks_per_beat) => {
+            ticks as f64 * tempo_us as f64 / 1_000_000.0 / ticks_per_beat.as_int().max(1) as f64
+        }
+        Timing::Timecode(fps, subframes) => ticks as f64 / (fps.as_f32() as f64 * subframes.max(1) as f64),
+    }
+}
+
+/**
+ * SMFのバイト列からタイムラインを作る
+ * 条件に合うノートを押している間をテープストップにし、テンポマップは BPM 同期の長さに使う
+ */
+pub fn timeline_from_smf(data: &[u8], trigger: &MidiTrigger) -> Result<Timeline, RenderError> {
+    let smf = Smf::parse(data)?;
+
+    // 全トラックのイベントを絶対ティックで並べる
+    // 同じティックではテンポ、ノートオフ、それ以外の順にする（レガートでノートオフとオンが並んでも、一度離してから押し直す）
+    let mut events = Vec::new();
+    for track in &smf.tracks {
+        let mut tick = 0u64;
+        for event in track {
+            tick += event.delta.as_int() as u64;
+            events.push((tick, event.kind));
+        }
+    }
+    events.sort_by_key(|(tick, kind)| {
+        let order = match kind {
+            TrackEventKind::Meta(MetaMessage::Tempo(_)) => 0,
+            TrackEventKind::Midi {
+                message: MidiMessage::NoteOff { .. },
+                ..
+            } => 1,
+            TrackEventKind::Midi {
+                message: MidiMessage::NoteOn { vel, .. },
+                ..
+            } if *vel == 0 => 1,
+            _ => 2,
+        };
+        (*tick, order)
+    });
+
+    let mut timeline = Timeline::default();
+    let mut tempo_us = DEFAULT_TEMPO_US;
+    let (mut last_tick, mut last_seconds) = (0u64, 0.0);
+    let mut held = 0usize; // 押さえられている対象ノートの数
+    for (tick, kind) in events {
+        let seconds = last_seconds + ticks_to_seconds(tick - last_tick, smf.header.timing, tempo_us);
+        (last_tick, last_seconds) = (tick, seconds);
+
+        match kind {
+            TrackEventKind::Meta(MetaMessage::Tempo(tempo)) => {
+                tempo_us = tempo.as_int().max(1);
+                let bpm = 60_000_000.0 / tempo_us as f64;
+                if tick == 0 {
+                    timeline.bpm = bpm;
+                } else {
+                    timeline.tempo.push(TempoChange { time: seconds, bpm });
+                }
+            }
+            TrackEventKind::Midi { channel, message } => {
+                let (key, pressed) = match message {
+                    MidiMessage::NoteOn { key, vel } => (key, vel > 0),
+                    MidiMessage::NoteOff { key, .. } => (key, false),
+                    _ => continue,
+                };
+                if !trigger.matches(channel.as_int(), key.as_int()) {
+                    continue;
+                }
+
+                if pressed {
+                    // 最初のノートで止め、すべて離したら再始動する
+                    if held == 0 {
+                        timeline.events.push(TimelineEvent {
+                            stop: seconds,
+                            start: None,
+                            ..trigger.event.clone()
+                        });
+                    }
+                    held += 1;
+                } else if held > 0 {
+                    held -= 1;
+                    if held == 0 {
+                        match timeline.events.last_mut() {
+                            // 長さのないノートは無視する
+                            Some(event) if seconds <= event.stop => {
+                                timeline.events.pop();
+                            }
+                            Some(event) => event.start = Some(seconds),
+                            None => (),
+                        }
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    timeline.validated()
+}
+
+/**
+ * SMFファイルからタイムラインを作る
+ */
+pub fn load_timeline(path: &Path, trigger: &MidiTrigger) -> Result<Timeline, RenderError> {
+    timeline_from_smf(&fs::read(path)?, trigger)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use midly::{Format, Header, TrackEvent};
+
+    fn note(delta: u32, channel: u8, key: u8, vel: u8) -> TrackEvent<'static> {
+        TrackEvent {
+            delta: delta.into(),
+            kind: TrackEventKind::Midi {
+                channel: channel.into(),
+                message: MidiMessage::NoteOn { key: key.into(), vel: vel.into() },
+            },
+        }
+    }
+
+    fn tempo(delta: u32, tempo_us: u32) -> TrackEvent<'static> {
+        TrackEvent {
+            delta: delta.into(),
+            kind: TrackEventKind::Meta(MetaMessage::Tempo(tempo_us.into())),
+        }
+    }
+
+    #[test]
+    fn notes_and_tempo_map_become_a_timeline() {
+        let mut smf = Smf::new(Header::new(Format::SingleTrack, Timing::Metrical(480.into())));
+        smf.tracks.push(vec![
+            tempo(0, 500_000),     // 120 BPM
+            note(480, 0, 36, 100), // 0.5 秒で止める
+            note(0, 1, 36, 100),   // 別のチャンネルは無視する
+            tempo(480, 1_000_000), // 1.0 秒から 60 BPM
+            note(480, 0, 36, 0),   // 60 BPM の1拍後（2.0 秒）に再始動
+            note(0, 1, 36, 0),
+            TrackEvent {
+                delta: 0.into(),
+                kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+            },
+        ]);
+        let mut data = Vec::new();
+        smf.write_std(&mut data).unwrap();
+
+        let trigger = MidiTrigger {
+            channel: Some(0),
+            note: Some(36),
+            ..MidiTrigger::default()
+        };
+        let timeline = timeline_from_smf(&data, &trigger).unwrap();
+
+        assert_eq!(timeline.bpm, 120.0);
+        assert_eq!(timeline.tempo, [TempoChange { time: 1.0, bpm: 60.0 }]);
+        assert_eq!(timeline.events.len(), 1);
+        assert!((timeline.events[0].stop - 0.5).abs() < 1.0e-9);
+        assert!((timeline.events[0].start.unwrap() - 2.0).abs() < 1.0e-9);
+    }
+
+    #[test]
+    fn legato_notes_restart_and_stop_on_the_same_tick() {
+        let mut smf = Smf::new(Header::new(Format::SingleTrack, Timing::Metrical(480.into())));
+        smf.tracks.push(vec![
+            note(480, 0, 36, 100), // 0.5 秒で止める
+            note(480, 0, 36, 0),   // 1.0 秒でノートオフとオンが同じティックに並ぶ
+            note(0, 0, 36, 100),
+            note(480, 0, 36, 100), // 1.5 秒ではオンが先に書かれていても、オフを先に扱う
+            note(0, 0, 36, 0),
+            note(480, 0, 36, 0), // 2.0 秒で再始動
+            TrackEvent {
+                delta: 0.into(),
+                kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+            },
+        ]);
+        let mut data = Vec::new();
+        smf.write_std(&mut data).unwrap();
+
+        let timeline = timeline_from_smf(&data, &MidiTrigger::default()).unwrap();
+        let spans: Vec<_> = timeline.events.iter().map(|event| (event.stop, event.start.unwrap())).collect();
+        assert_eq!(spans, [(0.5, 1.0), (1.0, 1.5), (1.5, 2.0)]);
+    }
+}
\ No newline at end of file
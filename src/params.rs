@@ -1,27 +1,35 @@
 ﻿use nih_plug::prelude::*;
+use std::sync::Arc;
 
-// カーブの種類
-#[derive(Enum, PartialEq, Clone, Copy, Debug)]
-pub enum TapeCurve {
-    Linear,      // 直線的
-    Smooth,      // 滑らか
-    SlowStart,   // 慣性あり
-    QuickCut,    // 急に落ち始め、最後にゆっくり
-}
+use crate::dsp::curve::TapeCurve;
+use crate::dsp::engine::{EngineParams, MAX_RAMP_SEC, MIN_PITCH_FLOOR_ST};
+use crate::dsp::sync::{HoldDivision, SyncBeat};
 
-// BPM同期用の拍数定義
+// オーバーサンプリング倍率
 #[derive(Enum, PartialEq, Clone, Copy)]
-pub enum SyncBeat {
-    #[name = "1/8"]
-    Eight,
-    #[name = "1/4"]
-    Quarter,
-    #[name = "1/2"]
-    Half,
-    #[name = "1 Bar"]
-    OneBar,
-    #[name = "2 Bars"]
-    TwoBars,
+pub enum Oversampling {
+    #[name = "Off"]
+    Off,
+    #[name = "2x"]
+    X2,
+    #[name = "4x"]
+    X4,
+    #[name = "8x"]
+    X8,
+}
+
+impl Oversampling {
+    /**
+     * 倍率を数値で取得
+     */
+    pub fn factor(self) -> usize {
+        match self {
+            Oversampling::Off => 1,
+            Oversampling::X2 => 2,
+            Oversampling::X4 => 4,
+            Oversampling::X8 => 8,
+        }
+    }
 }
 
 #[derive(Params)]
@@ -46,22 +54,287 @@ pub struct TapeStopParams {
 
     #[id = "enable_filter"]
     pub enable_filter: BoolParam, // ローパスフィルター
+
+    #[id = "follow_transport"]
+    pub follow_transport: BoolParam, // ホストの再生/停止でテープを止めるかどうか
+
+    #[id = "mix"]
+    pub mix: FloatParam, // ドライ/ウェット
+
+    #[id = "output_gain"]
+    pub output_gain: FloatParam, // 出力ゲイン
+
+    #[id = "enable_limiter"]
+    pub enable_limiter: BoolParam, // 出力段のソフトクリップ
+
+    #[id = "enable_wow"]
+    pub enable_wow: BoolParam, // ワウ・フラッター
+
+    #[id = "wow_rate"]
+    pub wow_rate: FloatParam, // ワウの周波数
+
+    #[id = "wow_depth"]
+    pub wow_depth: FloatParam, // 揺れの深さ
+
+    #[id = "wow_instability"]
+    pub wow_instability: FloatParam, // フラッターの乱雑さ
+
+    #[id = "enable_saturation"]
+    pub enable_saturation: BoolParam, // テープ飽和
+
+    #[id = "saturation_drive"]
+    pub saturation_drive: FloatParam, // 歪みの強さ
+
+    #[id = "saturation_bias"]
+    pub saturation_bias: FloatParam, // 非対称さ
+
+    #[id = "saturation_mix"]
+    pub saturation_mix: FloatParam, // 飽和段のミックス
+
+    #[id = "saturation_link"]
+    pub saturation_link: BoolParam, // 速度が落ちるほど drive を上げる
+
+    #[id = "enable_decimate"]
+    pub enable_decimate: BoolParam, // サンプルレート低下
+
+    #[id = "decimate_amount"]
+    pub decimate_amount: FloatParam, // 停止時の間引きの強さ
+
+    #[id = "enable_bitcrush"]
+    pub enable_bitcrush: BoolParam, // ビット深度低下
+
+    #[id = "bitcrush_amount"]
+    pub bitcrush_amount: FloatParam, // 停止時のビット削減の強さ
+
+    #[id = "lofi_anti_alias"]
+    pub lofi_anti_alias: BoolParam, // 間引き前のエイリアス除去
+
+    #[id = "stereo_spread"]
+    pub stereo_spread: FloatParam, // 左右で停止タイミングをずらす量
+
+    #[id = "enable_width"]
+    pub enable_width: BoolParam, // 減速に合わせてステレオ幅を狭める
+
+    #[id = "width_at_stop"]
+    pub width_at_stop: FloatParam, // 停止時のステレオ幅
+
+    #[id = "width_response"]
+    pub width_response: FloatParam, // 速度に対する幅の変化カーブ
+
+    #[id = "enable_noise"]
+    pub enable_noise: BoolParam, // テープノイズ
+
+    #[id = "hiss_level"]
+    pub hiss_level: FloatParam, // テープヒスの量
+
+    #[id = "crackle_level"]
+    pub crackle_level: FloatParam, // クラックルの量
+
+    #[id = "motor_level"]
+    pub motor_level: FloatParam, // モーター音の量
+
+    #[id = "enable_hold"]
+    pub enable_hold: BoolParam, // 停止位置でグレインをループする
+
+    #[id = "hold_division"]
+    pub hold_division: EnumParam<HoldDivision>, // グレインの長さ
+
+    #[id = "hold_crossfade"]
+    pub hold_crossfade: FloatParam, // ループのつなぎ目のクロスフェード
+
+    #[id = "hold_repeats"]
+    pub hold_repeats: IntParam, // ループ回数
+
+    #[id = "pitch_floor"]
+    pub pitch_floor: FloatParam, // PitchLinear で止まる直前の音程
+
+    #[id = "land_on_beat"]
+    pub land_on_beat: BoolParam, // 同期した長さぶんのテープをちょうど使い切って止める
+
+    #[id = "oversampling"]
+    pub oversampling: EnumParam<Oversampling>, // 内部処理のオーバーサンプリング倍率
 }
 
 impl Default for TapeStopParams {
     fn default() -> Self {
         Self {
-            trigger: BoolParam::new("Trigger", false)
-                .non_automatable(),
+            trigger: BoolParam::new("Trigger", false),
             use_sync: BoolParam::new("BPM Sync", false)
                 .non_automatable(),
-            stop_time: FloatParam::new("Stop Time (Sec)", 0.5, FloatRange::Linear { min: 0.1, max: 2.0 }),
+            stop_time: FloatParam::new("Stop Time (Sec)", 0.5, FloatRange::Linear { min: 0.1, max: MAX_RAMP_SEC })
+                .with_smoother(SmoothingStyle::Linear(50.0)),
             sync_beat: EnumParam::new("Stop Beat", SyncBeat::Quarter)
                 .non_automatable(),
-            start_time: FloatParam::new("Start Time", 0.5, FloatRange::Linear { min: 0.1, max: 2.0 }),
+            start_time: FloatParam::new("Start Time", 0.5, FloatRange::Linear { min: 0.1, max: MAX_RAMP_SEC })
+                .with_smoother(SmoothingStyle::Linear(50.0)),
             curve: EnumParam::new("Curve", TapeCurve::Linear)
                 .non_automatable(),
             enable_filter: BoolParam::new("Low-pass Effect", true),
+            follow_transport: BoolParam::new("Follow Transport", false)
+                .non_automatable(),
+            mix: FloatParam::new("Mix", 1.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_smoother(SmoothingStyle::Linear(20.0))
+                .with_unit("%")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+            output_gain: FloatParam::new(
+                "Output Gain",
+                util::db_to_gain(0.0),
+                FloatRange::Skewed {
+                    min: util::db_to_gain(-24.0),
+                    max: util::db_to_gain(12.0),
+                    factor: FloatRange::gain_skew_factor(-24.0, 12.0),
+                },
+            )
+                .with_smoother(SmoothingStyle::Logarithmic(20.0))
+                .with_unit(" dB")
+                .with_value_to_string(formatters::v2s_f32_gain_to_db(1))
+                .with_string_to_value(formatters::s2v_f32_gain_to_db()),
+            enable_limiter: BoolParam::new("Limiter", false),
+            enable_wow: BoolParam::new("Wow & Flutter", false),
+            wow_rate: FloatParam::new("Wow Rate", 0.8, FloatRange::Skewed { min: 0.1, max: 8.0, factor: FloatRange::skew_factor(-1.0) })
+                .with_smoother(SmoothingStyle::Logarithmic(50.0))
+                .with_unit(" Hz")
+                .with_value_to_string(formatters::v2s_f32_rounded(2)),
+            wow_depth: FloatParam::new("Wow Depth", 0.3, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_smoother(SmoothingStyle::Linear(20.0))
+                .with_unit("%")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+            wow_instability: FloatParam::new("Instability", 0.5, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_smoother(SmoothingStyle::Linear(20.0))
+                .with_unit("%")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+            enable_saturation: BoolParam::new("Saturation", false),
+            saturation_drive: FloatParam::new("Drive", 0.3, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_smoother(SmoothingStyle::Linear(20.0))
+                .with_unit("%")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+            saturation_bias: FloatParam::new("Bias", 0.2, FloatRange::Linear { min: -1.0, max: 1.0 })
+                .with_smoother(SmoothingStyle::Linear(20.0))
+                .with_value_to_string(formatters::v2s_f32_rounded(2)),
+            saturation_mix: FloatParam::new("Saturation Mix", 1.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_smoother(SmoothingStyle::Linear(20.0))
+                .with_unit("%")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+            saturation_link: BoolParam::new("Drive Follows Speed", true),
+            enable_decimate: BoolParam::new("Decimate", false),
+            decimate_amount: FloatParam::new("Decimate Amount", 0.5, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_smoother(SmoothingStyle::Linear(20.0))
+                .with_unit("%")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+            enable_bitcrush: BoolParam::new("Bitcrush", false),
+            bitcrush_amount: FloatParam::new("Bitcrush Amount", 0.5, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_smoother(SmoothingStyle::Linear(20.0))
+                .with_unit("%")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+            lofi_anti_alias: BoolParam::new("Anti-alias", true),
+            stereo_spread: FloatParam::new("Stereo Spread", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_smoother(SmoothingStyle::Linear(20.0))
+                .with_unit("%")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+            enable_width: BoolParam::new("Width Collapse", false),
+            width_at_stop: FloatParam::new("Width at Stop", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_smoother(SmoothingStyle::Linear(20.0))
+                .with_unit("%")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+            width_response: FloatParam::new("Width Response", 1.0, FloatRange::Skewed { min: 0.25, max: 4.0, factor: FloatRange::skew_factor(-1.0) })
+                .with_smoother(SmoothingStyle::Logarithmic(20.0))
+                .with_value_to_string(formatters::v2s_f32_rounded(2)),
+            enable_noise: BoolParam::new("Noise", false),
+            hiss_level: FloatParam::new("Hiss", 0.3, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_smoother(SmoothingStyle::Linear(20.0))
+                .with_unit("%")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+            crackle_level: FloatParam::new("Crackle", 0.2, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_smoother(SmoothingStyle::Linear(20.0))
+                .with_unit("%")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+            motor_level: FloatParam::new("Motor", 0.2, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_smoother(SmoothingStyle::Linear(20.0))
+                .with_unit("%")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+            enable_hold: BoolParam::new("Hold", false),
+            hold_division: EnumParam::new("Hold Grain", HoldDivision::Sixteenth)
+                .non_automatable(),
+            hold_crossfade: FloatParam::new("Hold Crossfade", 0.1, FloatRange::Linear { min: 0.0, max: 0.5 })
+                .with_smoother(SmoothingStyle::Linear(20.0))
+                .with_unit("%")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+            hold_repeats: IntParam::new("Hold Repeats", 0, IntRange::Linear { min: 0, max: 16 })
+                .with_value_to_string(Arc::new(|value| {
+                    if value == 0 { String::from("∞") } else { value.to_string() }
+                })),
+            pitch_floor: FloatParam::new("Pitch Floor", -36.0, FloatRange::Linear { min: MIN_PITCH_FLOOR_ST, max: -12.0 })
+                .with_step_size(1.0)
+                .with_unit(" st"),
+            land_on_beat: BoolParam::new("Land on Beat", false)
+                .non_automatable(),
+            oversampling: EnumParam::new("Oversampling", Oversampling::Off)
+                .non_automatable(),
+        }
+    }
+}
+
+impl TapeStopParams {
+    /**
+     * 今のパラメータからエンジンに渡す値を作る
+     * - trigger ノートやキーボードなども含めたトリガー
+     * - bpm 同期に使うテンポ
+     * - read 連続値の読み方（プラグインではスムーザーを進め、ヘッドレスではそのままの値を使う）
+     */
+    pub fn engine_params(&self, trigger: bool, bpm: f64, mut read: impl FnMut(&FloatParam) -> f32) -> EngineParams {
+        EngineParams {
+            trigger,
+            stop_time_sec: read(&self.stop_time),
+            start_time_sec: read(&self.start_time),
+            curve: self.curve.value(),
+            use_sync: self.use_sync.value(),
+            sync_beat: self.sync_beat.value(),
+            bpm,
+            enable_filter: self.enable_filter.value(),
+            mix: read(&self.mix),
+            output_gain: read(&self.output_gain),
+            enable_limiter: self.enable_limiter.value(),
+            enable_wow: self.enable_wow.value(),
+            wow_rate: read(&self.wow_rate),
+            wow_depth: read(&self.wow_depth),
+            wow_instability: read(&self.wow_instability),
+            enable_saturation: self.enable_saturation.value(),
+            saturation_drive: read(&self.saturation_drive),
+            saturation_bias: read(&self.saturation_bias),
+            saturation_mix: read(&self.saturation_mix),
+            saturation_link: self.saturation_link.value(),
+            enable_decimate: self.enable_decimate.value(),
+            decimate_amount: read(&self.decimate_amount),
+            enable_bitcrush: self.enable_bitcrush.value(),
+            bitcrush_amount: read(&self.bitcrush_amount),
+            lofi_anti_alias: self.lofi_anti_alias.value(),
+            stereo_spread: read(&self.stereo_spread),
+            enable_width: self.enable_width.value(),
+            width_at_stop: read(&self.width_at_stop),
+            width_response: read(&self.width_response),
+            enable_noise: self.enable_noise.value(),
+            hiss_level: read(&self.hiss_level),
+            crackle_level: read(&self.crackle_level),
+            motor_level: read(&self.motor_level),
+            enable_hold: self.enable_hold.value(),
+            hold_division: self.hold_division.value(),
+            hold_crossfade: read(&self.hold_crossfade),
+            hold_repeats: self.hold_repeats.value() as u32,
+            pitch_floor_st: self.pitch_floor.value(),
+            land_on_beat: self.land_on_beat.value(),
         }
     }
 }
\ No newline at end of file
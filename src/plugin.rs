@@ -0,0 +1,408 @@
+use nih_plug::prelude::*;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+#[cfg(feature = "editor")]
+use nih_plug_egui::EguiState;
+
+use crate::dsp::engine::{TapeState, TapeStopEngine, MIN_SYNC_BPM};
+use crate::dsp::oversampling::{OversampledEngine, MAX_OVERSAMPLING};
+use crate::params::TapeStopParams;
+#[cfg(feature = "editor")]
+use crate::editor;
+#[cfg(feature = "standalone")]
+use crate::standalone;
+
+pub(crate) struct TapeStop {
+    params: Arc<TapeStopParams>,
+    engines: Vec<OversampledEngine>, // 倍率ごとのエンジン（[i] が 2^i 倍、このサンプルレートで使える倍率まで）
+    active: usize,                   // 使っているエンジンの番号
+    #[cfg(feature = "editor")]
+    editor_state: Arc<EguiState>,
+    peak_meter: Arc<AtomicU32>,
+
+    #[cfg(feature = "standalone")]
+    sample_rate: f32,       // ホストのサンプルレート（ループ入力の再生速度に使う）
+    max_seconds: f32,       // 遅延バッファの長さ（秒）
+    clamp_warned: bool,     // 停止時間の切り詰めを知らせたかどうか
+    reported_latency: u32,  // ホストに伝えた遅延サンプル数
+    held_notes: u32,        // 押さえられているノートの数（ノートでもトリガーできる）
+    #[cfg(feature = "standalone")]
+    loop_player: Option<standalone::LoopPlayer>, // スタンドアロンで入力の代わりに流すWAV
+    input_channels: usize,  // ホストと合意した入力チャンネル数
+    input_frame: Vec<f32>,  // 1フレーム分の入力
+    output_frame: Vec<f32>, // 1フレーム分の出力
+}
+
+impl Default for TapeStop {
+    fn default() -> Self {
+        Self {
+            params: Arc::new(TapeStopParams::default()),
+            engines: Vec::new(),
+            active: 0,
+            #[cfg(feature = "editor")]
+            editor_state: EguiState::from_size(600, 500),
+            peak_meter: Arc::new(AtomicU32::new(0f32.to_bits())),
+            #[cfg(feature = "standalone")]
+            sample_rate: 0.0,
+            max_seconds: TapeStopEngine::<f32>::required_seconds(MIN_SYNC_BPM),
+            clamp_warned: false,
+            reported_latency: 0,
+            held_notes: 0,
+            #[cfg(feature = "standalone")]
+            loop_player: standalone::LoopPlayer::new(),
+            input_channels: 0,
+            input_frame: Vec::new(),
+            output_frame: Vec::new(),
+        }
+    }
+}
+
+impl TapeStop {
+    /**
+     * 選ばれている倍率のエンジンの番号（このサンプルレートで使えない倍率は、使える最大の倍率にする）
+     */
+    fn engine_index(&self) -> usize {
+        let stage = self.params.oversampling.value().factor().trailing_zeros() as usize;
+        stage.min(self.engines.len().saturating_sub(1))
+    }
+}
+
+impl Plugin for TapeStop {
+    const NAME: &'static str = "Kyun'Stop";
+    const VENDOR: &'static str = "Goto Kenta";
+    const URL: &'static str = "https://koto-thing.com/portfolio/";
+    const EMAIL: &'static str = "gotoukenta62@gmail.com";
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    // 音声入出力の設定（先頭がデフォルト）
+    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[
+        // ステレオ
+        AudioIOLayout {
+            main_input_channels: NonZeroU32::new(2),
+            main_output_channels: NonZeroU32::new(2),
+            ..AudioIOLayout::const_default()
+        },
+        // モノラル
+        AudioIOLayout {
+            main_input_channels: NonZeroU32::new(1),
+            main_output_channels: NonZeroU32::new(1),
+            ..AudioIOLayout::const_default()
+        },
+        // モノラル -> ステレオ
+        AudioIOLayout {
+            main_input_channels: NonZeroU32::new(1),
+            main_output_channels: NonZeroU32::new(2),
+            ..AudioIOLayout::const_default()
+        },
+        // クアッド
+        AudioIOLayout {
+            main_input_channels: NonZeroU32::new(4),
+            main_output_channels: NonZeroU32::new(4),
+            ..AudioIOLayout::const_default()
+        },
+        // 5.1
+        AudioIOLayout {
+            main_input_channels: NonZeroU32::new(6),
+            main_output_channels: NonZeroU32::new(6),
+            ..AudioIOLayout::const_default()
+        },
+        // 7.1
+        AudioIOLayout {
+            main_input_channels: NonZeroU32::new(8),
+            main_output_channels: NonZeroU32::new(8),
+            ..AudioIOLayout::const_default()
+        },
+    ];
+
+    // ノートオンでテープストップ、ノートオフで再始動
+    const MIDI_INPUT: MidiConfig = MidiConfig::Basic;
+    const SAMPLE_ACCURATE_AUTOMATION: bool = true;
+
+    type SysExMessage = ();
+    type BackgroundTask = ();
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn initialize(
+        &mut self,
+        audio_io_layout: &AudioIOLayout,
+        buffer_config: &BufferConfig,
+        context: &mut impl InitContext<Self>,
+    ) -> bool {
+        // 合意したレイアウトからチャンネル数を決める
+        let output_channels = audio_io_layout
+            .main_output_channels
+            .map(NonZeroU32::get)
+            .unwrap_or(0) as usize;
+        let input_channels = audio_io_layout
+            .main_input_channels
+            .map(NonZeroU32::get)
+            .unwrap_or(0) as usize;
+        if output_channels == 0 {
+            return false;
+        }
+
+        self.input_channels = input_channels.min(output_channels);
+        self.input_frame = vec![0.0; output_channels];
+        self.output_frame = vec![0.0; output_channels];
+
+        #[cfg(feature = "standalone")]
+        {
+            self.sample_rate = buffer_config.sample_rate;
+        }
+        // 倍率を変えても process で確保せずに切り替えられるよう、使える倍率すべてのエンジンをここで用意する
+        // 倍率は内部のサンプルレートの上限までに抑えるので、高いサンプルレートでは用意するエンジンも減る
+        let max_factor = OversampledEngine::<f32>::supported_factor(buffer_config.sample_rate, MAX_OVERSAMPLING);
+        let count = max_factor.trailing_zeros() as usize + 1;
+        if self.engines.len() == count && self.engines.iter().all(|engine| engine.channels() == output_channels) {
+            // チャンネル数と倍率が同じなら、確保済みのバッファを使い回す
+            for engine in &mut self.engines {
+                engine.prepare(buffer_config.sample_rate, self.max_seconds);
+            }
+        } else {
+            self.engines = (0..count)
+                .map(|stage| {
+                    OversampledEngine::new(
+                        buffer_config.sample_rate,
+                        self.max_seconds, // 最低テンポでの最長の停止に合わせた長さ
+                        output_channels,
+                        1 << stage,
+                    )
+                })
+                .collect();
+        }
+
+        self.active = self.engine_index();
+        self.reported_latency = self.engines[self.active].latency();
+        context.set_latency_samples(self.reported_latency);
+        true
+    }
+
+    fn reset(&mut self) {
+        if let Some(engine) = self.engines.get_mut(self.active) {
+            engine.reset();
+        }
+        self.held_notes = 0;
+        #[cfg(feature = "standalone")]
+        if let Some(player) = &mut self.loop_player {
+            player.reset();
+        }
+    }
+
+    fn process(
+        &mut self,
+        buffer: &mut Buffer,
+        _aux: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        if self.engines.is_empty() {
+            return ProcessStatus::Normal;
+        }
+
+        // 倍率が変わったら initialize で用意しておいたエンジンに切り替え、そのエンジンの遅延をホストに伝える
+        // ホストが initialize し直さなくても、報告する遅延と実際の遅延は常に一致する
+        let index = self.engine_index();
+        if index != self.active {
+            self.active = index;
+            self.engines[index].reset(); // 確保はせず、前に使っていたときの状態だけを消す
+            let latency = self.engines[index].latency();
+            if latency != self.reported_latency {
+                self.reported_latency = latency;
+                context.set_latency_samples(latency);
+            }
+        }
+        let engine = &mut self.engines[self.active];
+
+        // BPMをホストから取得
+        let bpm = context.transport().tempo.unwrap_or(120.0);
+
+        // ホストのトランスポートが止まっていればテープも止める
+        let transport_stopped = self.params.follow_transport.value() && !context.transport().playing;
+        #[cfg(feature = "standalone")]
+        let key_trigger = standalone::key_trigger();
+        #[cfg(not(feature = "standalone"))]
+        let key_trigger = false;
+        let mut max_amplitude: f32 = 0.0;
+        let mut next_event = context.next_event();
+
+        // バッファ内の全サンプルを処理する
+        for (sample_id, mut channel_samples) in buffer.iter_samples().enumerate() {
+            // このサンプルまでに届いたノートを反映する
+            while let Some(event) = next_event {
+                if event.timing() > sample_id as u32 {
+                    break;
+                }
+                match event {
+                    NoteEvent::NoteOn { .. } => self.held_notes += 1,
+                    NoteEvent::NoteOff { .. } => self.held_notes = self.held_notes.saturating_sub(1),
+                    NoteEvent::Choke { .. } => self.held_notes = 0,
+                    _ => (),
+                }
+                next_event = context.next_event();
+            }
+
+            // パラメータをとってくる
+            let trigger = self.params.trigger.value() || self.held_notes > 0 || key_trigger || transport_stopped;
+            let engine_params = self.params.engine_params(trigger, bpm, |param| param.smoothed.next());
+
+            // 入力を一時的にコピーしておく
+            for (i, sample) in channel_samples.iter_mut().enumerate() {
+                if i < self.input_channels {
+                    self.input_frame[i] = *sample;
+                } else if self.input_channels == 1 {
+                    // モノラル -> ステレオでは入力を全チャンネルに広げる
+                    self.input_frame[i] = self.input_frame[0];
+                } else {
+                    self.input_frame[i] = 0.0;
+                }
+            }
+            #[cfg(feature = "standalone")]
+            if let Some(player) = &mut self.loop_player {
+                player.next(self.sample_rate, &mut self.input_frame);
+            }
+
+            // DSPエンジンで処理
+            engine.process(&self.input_frame, &mut self.output_frame, &engine_params);
+
+            // 最大振幅を計算
+            for sample in self.output_frame.iter() {
+                let abs = sample.abs();
+                if abs > max_amplitude {
+                    max_amplitude = abs;
+                }
+            }
+
+            // 結果をバッファに書き戻す
+            for (sample, out) in channel_samples.iter_mut().zip(self.output_frame.iter()) {
+                *sample = *out;
+            }
+        }
+
+        self.peak_meter.store(max_amplitude.to_bits(), Ordering::Relaxed);
+
+        // テンポが遅すぎて同期の長さがバッファに収まらないときは、切り詰めたことを一度だけ知らせる
+        let clamped = engine.is_clamped();
+        if clamped && !self.clamp_warned {
+            nih_warn!(
+                "Synced stop does not fit in the {:.1} s tape buffer at {:.1} BPM; clamping the stop time",
+                self.max_seconds,
+                bpm
+            );
+        }
+        self.clamp_warned = clamped;
+
+        // ランプやクロスフェードを途中で切られないよう、テープの状態をホストに伝える
+        match engine.state() {
+            // 何もしていないときはホストがスリープさせてよい
+            TapeState::Playing => ProcessStatus::Normal,
+            // トリガー中はいつ再始動するかわからないので、バッファを保持したまま処理を続けてもらう
+            TapeState::Stopping | TapeState::Stopped => ProcessStatus::KeepAlive,
+            // 再始動とクロスフェードの残り
+            TapeState::Starting | TapeState::Crossfading => ProcessStatus::Tail(engine.tail_samples()),
+        }
+    }
+
+    #[cfg(feature = "editor")]
+    fn editor(&mut self, async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
+        editor::create(
+            self.params.clone(),
+            self.peak_meter.clone(),
+            self.editor_state.clone(),
+        )
+    }
+}
+
+impl Vst3Plugin for TapeStop {
+    const VST3_CLASS_ID: [u8; 16] = *b"TapeStopPlugin12";
+    const VST3_SUBCATEGORIES: &'static [Vst3SubCategory] = &[Vst3SubCategory::Fx, Vst3SubCategory::Modulation];
+}
+
+impl ClapPlugin for TapeStop {
+    const CLAP_ID: &'static str = "com.koto-thing.kyun2stop";
+    const CLAP_DESCRIPTION: Option<&'static str> = Some("Tape stop / start effect");
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    const CLAP_FEATURES: &'static [ClapFeature] = &[
+        ClapFeature::AudioEffect,
+        ClapFeature::Stereo,
+        ClapFeature::Mono,
+        ClapFeature::Surround,
+        ClapFeature::Glitch,
+    ];
+}
+
+nih_export_clap!(TapeStop);
+nih_export_vst3!(TapeStop);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap_sys::entry::clap_plugin_entry;
+    use clap_sys::ext::params::{clap_param_info, clap_plugin_params, CLAP_EXT_PARAMS, CLAP_PARAM_IS_AUTOMATABLE};
+    use clap_sys::factory::plugin_factory::{clap_plugin_factory, CLAP_PLUGIN_FACTORY_ID};
+    use clap_sys::host::clap_host;
+    use clap_sys::version::CLAP_VERSION;
+    use std::collections::HashMap;
+    use std::ffi::{c_char, c_void, CStr};
+    use std::ptr;
+
+    // 拡張を何も持たないホスト
+    unsafe extern "C" fn host_get_extension(_host: *const clap_host, _id: *const c_char) -> *const c_void {
+        ptr::null()
+    }
+
+    unsafe extern "C" fn host_request(_host: *const clap_host) {}
+
+    #[test]
+    fn clap_entry_enumerates_parameters() {
+        let host = clap_host {
+            clap_version: CLAP_VERSION,
+            host_data: ptr::null_mut(),
+            name: c"k2s-test".as_ptr(),
+            vendor: c"".as_ptr(),
+            url: c"".as_ptr(),
+            version: c"0.0.0".as_ptr(),
+            get_extension: Some(host_get_extension),
+            request_restart: Some(host_request),
+            request_process: Some(host_request),
+            request_callback: Some(host_request),
+        };
+
+        let mut flags: HashMap<String, u32> = HashMap::new();
+        unsafe {
+            // エクスポートされたシンボルを、ホストと同じく C のレイアウトとして読む
+            let entry = &*(ptr::addr_of!(clap_entry) as *const clap_plugin_entry);
+            assert!((entry.init.unwrap())(c"".as_ptr()));
+
+            let factory = (entry.get_factory.unwrap())(CLAP_PLUGIN_FACTORY_ID.as_ptr()) as *const clap_plugin_factory;
+            assert!(!factory.is_null());
+            assert_eq!(((*factory).get_plugin_count.unwrap())(factory), 1);
+            let descriptor = ((*factory).get_plugin_descriptor.unwrap())(factory, 0);
+            assert_eq!(CStr::from_ptr((*descriptor).id).to_str().unwrap(), TapeStop::CLAP_ID);
+
+            let plugin = ((*factory).create_plugin.unwrap())(factory, &host, (*descriptor).id);
+            assert!(!plugin.is_null());
+            assert!(((*plugin).init.unwrap())(plugin));
+
+            let params = ((*plugin).get_extension.unwrap())(plugin, CLAP_EXT_PARAMS.as_ptr()) as *const clap_plugin_params;
+            assert!(!params.is_null());
+            for index in 0..((*params).count.unwrap())(plugin) {
+                let mut info: clap_param_info = std::mem::zeroed();
+                assert!(((*params).get_info.unwrap())(plugin, index, &mut info));
+                let name = CStr::from_ptr(info.name.as_ptr()).to_string_lossy().into_owned();
+                flags.insert(name, info.flags);
+            }
+
+            ((*plugin).destroy.unwrap())(plugin);
+            (entry.deinit.unwrap())();
+        }
+
+        // Rust 側のパラメータがすべて見えていて、トリガーはホストから動かせる
+        assert_eq!(flags.len(), TapeStopParams::default().param_map().len());
+        assert_ne!(flags["Trigger"] & CLAP_PARAM_IS_AUTOMATABLE, 0);
+        assert_ne!(flags["Stop Time (Sec)"] & CLAP_PARAM_IS_AUTOMATABLE, 0);
+        assert_eq!(flags["Oversampling"] & CLAP_PARAM_IS_AUTOMATABLE, 0);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,490 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::dsp::curve::TapeCurve;
+use crate::dsp::engine::{EngineParams, TapeState, TapeStopEngine, MIN_SYNC_BPM};
+use crate::dsp::sync::SyncBeat;
+
+/**
+ * オフラインレンダリングのエラー
+ */
+#[derive(Debug)]
+pub enum RenderError {
+    Io(io::Error),       // ファイルの読み書き
+    Wav(hound::Error),   // WAVの読み書き
+    Midi(midly::Error),  // SMFの読み込み
+    Timeline(String),    // タイムラインの読み込み・検証
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenderError::Io(err) => write!(f, "{err}"),
+            RenderError::Wav(err) => write!(f, "WAV error: {err}"),
+            RenderError::Midi(err) => write!(f, "MIDI error: {err}"),
+            RenderError::Timeline(message) => write!(f, "timeline error: {message}"),
+        }
+    }
+}
+
+impl Error for RenderError {}
+
+impl From<io::Error> for RenderError {
+    fn from(err: io::Error) -> Self {
+        RenderError::Io(err)
+    }
+}
+
+impl From<hound::Error> for RenderError {
+    fn from(err: hound::Error) -> Self {
+        RenderError::Wav(err)
+    }
+}
+
+impl From<midly::Error> for RenderError {
+    fn from(err: midly::Error) -> Self {
+        RenderError::Midi(err)
+    }
+}
+
+/**
+ * 書き出すWAVのサンプル形式
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    Int16,   // 16-bit 整数
+    Int24,   // 24-bit 整数
+    Float32, // 32-bit 浮動小数点
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "16" => Ok(OutputFormat::Int16),
+            "24" => Ok(OutputFormat::Int24),
+            "float" | "32f" => Ok(OutputFormat::Float32),
+            _ => Err(format!("unknown output format `{s}` (expected 16, 24 or float)")),
+        }
+    }
+}
+
+/**
+ * インターリーブされたオーディオ（-1.0〜1.0）
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Audio {
+    pub samples: Vec<f32>, // インターリーブされたサンプル
+    pub channels: usize,   // チャンネル数
+    pub sample_rate: u32,  // サンプルレート
+}
+
+impl Audio {
+    /**
+     * WAVファイルを読み込む（整数は -1.0〜1.0 に変換する）
+     */
+    pub fn read(path: &Path) -> Result<Self, RenderError> {
+        let mut reader = hound::WavReader::open(path)?;
+        let spec = reader.spec();
+        let samples = match spec.sample_format {
+            hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<Vec<_>, _>>()?,
+            hound::SampleFormat::Int => {
+                let scale = 1.0 / (1u64 << (spec.bits_per_sample - 1)) as f32;
+                reader
+                    .samples::<i32>()
+                    .map(|sample| sample.map(|s| s as f32 * scale))
+                    .collect::<Result<Vec<_>, _>>()?
+            }
+        };
+
+        Ok(Self {
+            samples,
+            channels: spec.channels as usize,
+            sample_rate: spec.sample_rate,
+        })
+    }
+
+    /**
+     * WAVファイルに書き出す（整数形式では範囲外をクリップする）
+     */
+    pub fn write(&self, path: &Path, format: OutputFormat) -> Result<(), RenderError> {
+        let (bits_per_sample, sample_format) = match format {
+            OutputFormat::Int16 => (16, hound::SampleFormat::Int),
+            OutputFormat::Int24 => (24, hound::SampleFormat::Int),
+            OutputFormat::Float32 => (32, hound::SampleFormat::Float),
+        };
+        let spec = hound::WavSpec {
+            channels: self.channels as u16,
+            sample_rate: self.sample_rate,
+            bits_per_sample,
+            sample_format,
+        };
+
+        let mut writer = hound::WavWriter::create(path, spec)?;
+        match format {
+            OutputFormat::Float32 => {
+                for &sample in &self.samples {
+                    writer.write_sample(sample)?;
+                }
+            }
+            OutputFormat::Int16 | OutputFormat::Int24 => {
+                let max = ((1i32 << (bits_per_sample - 1)) - 1) as f32;
+                for &sample in &self.samples {
+                    writer.write_sample((sample.clamp(-1.0, 1.0) * max).round() as i32)?;
+                }
+            }
+        }
+        writer.finalize()?;
+        Ok(())
+    }
+
+    /**
+     * フレーム数を取得
+     */
+    pub fn frames(&self) -> usize {
+        self.samples.len() / self.channels.max(1)
+    }
+}
+
+fn default_bpm() -> f64 {
+    120.0
+}
+
+/**
+ * テープストップを掛ける時刻のリスト
+ */
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Timeline {
+    #[serde(default = "default_bpm")]
+    pub bpm: f64, // BPM同期に使うテンポ
+    #[serde(default)]
+    pub tempo: Vec<TempoChange>, // 途中のテンポ変化
+    #[serde(default)]
+    pub events: Vec<TimelineEvent>, // 停止/再始動のイベント
+}
+
+/**
+ * テンポ変化（その時刻から次の変化までのテンポ）
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TempoChange {
+    pub time: f64, // 時刻（秒）
+    pub bpm: f64,  // テンポ
+}
+
+/**
+ * 1回のテープストップ（省略した項目はプラグインの初期値になる）
+ */
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TimelineEvent {
+    pub stop: f64,                // トリガーする時刻（秒）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start: Option<f64>,       // 再始動する時刻（秒）。省略すると最後まで止めたまま
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub curve: Option<TapeCurve>, // カーブの種類
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_time: Option<f32>,   // 停止時間（秒）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_time: Option<f32>,  // 再始動時間（秒）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sync: Option<SyncBeat>,   // 指定するとBPM同期した長さで止める（stop_time より優先）
+    #[serde(default)]
+    pub land_on_beat: bool,       // BPM同期時、拍の頭でちょうど止まるようにする
+}
+
+impl Default for Timeline {
+    fn default() -> Self {
+        Self {
+            bpm: default_bpm(),
+            tempo: Vec::new(),
+            events: Vec::new(),
+        }
+    }
+}
+
+impl Timeline {
+    /**
+     * 拡張子（.json / .toml）に合わせてタイムラインを読み込む
+     */
+    pub fn load(path: &Path) -> Result<Self, RenderError> {
+        let text = fs::read_to_string(path)?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Self::from_toml(&text),
+            Some("json") => Self::from_json(&text),
+            _ => Err(RenderError::Timeline(format!("{} is neither .json nor .toml", path.display()))),
+        }
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, RenderError> {
+        let timeline: Self = serde_json::from_str(text).map_err(|err| RenderError::Timeline(err.to_string()))?;
+        timeline.validated()
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, RenderError> {
+        let timeline: Self = toml::from_str(text).map_err(|err| RenderError::Timeline(err.to_string()))?;
+        timeline.validated()
+    }
+
+    /**
+     * イベントを時刻順に並べ、重なりや範囲外の値がないか確かめる
+     */
+    pub(crate) fn validated(mut self) -> Result<Self, RenderError> {
+        for bpm in std::iter::once(self.bpm).chain(self.tempo.iter().map(|change| change.bpm)) {
+            if bpm.is_nan() || bpm <= 0.0 {
+                return Err(RenderError::Timeline(format!("bpm must be positive, got {bpm}")));
+            }
+        }
+        self.tempo.sort_by(|a, b| a.time.total_cmp(&b.time));
+        self.events.sort_by(|a, b| a.stop.total_cmp(&b.stop));
+
+        let mut previous_end = 0.0;
+        for event in &self.events {
+            if event.stop.is_nan() || event.stop < 0.0 {
+                return Err(RenderError::Timeline(format!("stop times must be >= 0, got {}", event.stop)));
+            }
+            if event.stop < previous_end {
+                return Err(RenderError::Timeline(format!("the stop at {} s overlaps the previous event", event.stop)));
+            }
+            match event.start {
+                Some(start) if start <= event.stop => {
+                    return Err(RenderError::Timeline(format!("the stop at {} s restarts before it stops", event.stop)));
+                }
+                Some(start) => previous_end = start,
+                None => previous_end = f64::INFINITY,
+            }
+            for time in [event.stop_time, event.start_time].into_iter().flatten() {
+                if time.is_nan() || time <= 0.0 {
+                    return Err(RenderError::Timeline(format!("ramp times must be positive, got {time}")));
+                }
+            }
+        }
+        Ok(self)
+    }
+}
+
+impl TimelineEvent {
+    /**
+     * このイベントで使うエンジンのパラメータ
+     */
+    fn params(&self, bpm: f64) -> EngineParams {
+        let defaults = EngineParams::default();
+        EngineParams {
+            trigger: true,
+            stop_time_sec: self.stop_time.unwrap_or(defaults.stop_time_sec),
+            start_time_sec: self.start_time.unwrap_or(defaults.start_time_sec),
+            curve: self.curve.unwrap_or(defaults.curve),
+            use_sync: self.sync.is_some(),
+            sync_beat: self.sync.unwrap_or(defaults.sync_beat),
+            bpm,
+            land_on_beat: self.land_on_beat,
+            ..defaults
+        }
+    }
+}
+
+/**
+ * 秒をフレーム位置に変換
+ */
+fn to_frame(seconds: f64, sample_rate: u32) -> usize {
+    (seconds * sample_rate as f64).round() as usize
+}
+
+/**
+ * タイムラインに沿ってテープストップを掛けたオーディオを作る
+ * 出力の長さは入力と同じ
+ */
+pub fn render(input: &Audio, timeline: &Timeline) -> Audio {
+    let channels = input.channels.max(1);
+    let max_seconds = TapeStopEngine::<f32>::required_seconds(MIN_SYNC_BPM);
+    let mut engine = TapeStopEngine::<f32>::new(input.sample_rate as f32, max_seconds, channels);
+
+    let mut samples = vec![0.0; input.samples.len()];
+    let mut params = EngineParams::default();
+    let mut bpm = timeline.bpm;
+    let mut tempo = timeline.tempo.iter().peekable();
+    let mut events = timeline.events.iter().peekable();
+    let mut release = None; // 再始動するフレーム
+    let mut deferred = false; // 再始動が終わるまで次の停止を待たせているかどうか
+
+    let frames = input.samples.chunks(channels).zip(samples.chunks_mut(channels));
+    for (frame, (in_frame, out_frame)) in frames.enumerate() {
+        // テンポマップをたどる（同期の長さはトリガーした瞬間のテンポで決まる）
+        while let Some(change) = tempo.next_if(|change| to_frame(change.time, input.sample_rate) <= frame) {
+            bpm = change.bpm;
+        }
+
+        // 再始動の時刻で離す
+        // 同じ時刻に次の停止があるときは、再始動を最後まで鳴らしてから止める（そのまま止めると再始動が消える）
+        if release.is_some_and(|release| frame >= release) {
+            params.trigger = false;
+            release = None;
+            deferred = events.peek().is_some_and(|event| to_frame(event.stop, input.sample_rate) <= frame);
+        }
+        if deferred && matches!(engine.state(), TapeState::Crossfading | TapeState::Playing) {
+            deferred = false;
+        }
+
+        // 次のイベントの時刻になったらトリガーする
+        while let Some(event) = events.next_if(|event| !deferred && to_frame(event.stop, input.sample_rate) <= frame) {
+            params = event.params(bpm);
+            release = event.start.map(|start| to_frame(start, input.sample_rate));
+        }
+        params.bpm = bpm;
+
+        engine.process(in_frame, out_frame, &params);
+    }
+
+    Audio {
+        samples,
+        channels,
+        sample_rate: input.sample_rate,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 8_000;
+
+    fn sine(seconds: f64) -> Audio {
+        let frames = (seconds * SAMPLE_RATE as f64) as usize;
+        let samples = (0..frames)
+            .flat_map(|i| {
+                let value = (std::f64::consts::TAU * 220.0 * i as f64 / SAMPLE_RATE as f64).sin() as f32 * 0.5;
+                [value, -value]
+            })
+            .collect();
+        Audio {
+            samples,
+            channels: 2,
+            sample_rate: SAMPLE_RATE,
+        }
+    }
+
+    // from -> to 秒の間で、1サンプルごとの変化の最大値（止まっていれば 0 に近い）
+    fn max_step(audio: &Audio, from: f64, to: f64) -> f32 {
+        let window = &audio.samples[to_frame(from, SAMPLE_RATE) * 2..to_frame(to, SAMPLE_RATE) * 2];
+        window.chunks(2).collect::<Vec<_>>().windows(2).map(|pair| (pair[0][0] - pair[1][0]).abs()).fold(0.0, f32::max)
+    }
+
+    #[test]
+    fn sine_round_trips_through_a_wav_file() {
+        let input = sine(0.5);
+        // イベントがなければエンジンは素通しになる
+        let output = render(&input, &Timeline::default());
+        assert_eq!(output, input);
+
+        for (format, tolerance) in [
+            (OutputFormat::Int16, 2.0 / 32_767.0),
+            (OutputFormat::Int24, 2.0 / 8_388_607.0),
+            (OutputFormat::Float32, 0.0),
+        ] {
+            let path = std::env::temp_dir().join(format!("k2s_render_{}_{format:?}.wav", std::process::id()));
+            output.write(&path, format).unwrap();
+            let read = Audio::read(&path).unwrap();
+            fs::remove_file(&path).unwrap();
+
+            assert_eq!((read.channels, read.sample_rate, read.frames()), (2, SAMPLE_RATE, input.frames()));
+            for (a, b) in read.samples.iter().zip(&input.samples) {
+                assert!((a - b).abs() <= tolerance, "{format:?}: {a} vs {b}");
+            }
+        }
+    }
+
+    #[test]
+    fn timeline_stops_and_restarts_the_tape() {
+        let timeline = Timeline::from_toml(
+            r#"
+            bpm = 120.0
+
+            [[events]]
+            stop = 0.5
+            start = 1.0
+            curve = "quick_cut"
+            stop_time = 0.2
+            start_time = 0.2
+            "#,
+        )
+        .unwrap();
+        let input = sine(1.5);
+        let output = render(&input, &timeline);
+
+        // 止まりきってから再始動するまでは、音程のない一定の値になる
+        let stopped = &output.samples[to_frame(0.75, SAMPLE_RATE) * 2..to_frame(1.0, SAMPLE_RATE) * 2];
+        for pair in stopped.chunks(2).collect::<Vec<_>>().windows(2) {
+            assert!((pair[0][0] - pair[1][0]).abs() < 1.0e-3);
+        }
+
+        // 再始動とクロスフェードが終われば入力に戻る
+        let tail = to_frame(1.4, SAMPLE_RATE) * 2;
+        assert_eq!(output.samples[tail..], input.samples[tail..]);
+    }
+
+    #[test]
+    fn synced_stop_uses_the_tempo_at_the_trigger() {
+        let timeline = Timeline::from_toml(
+            r#"
+            bpm = 120.0
+            tempo = [{ time = 0.25, bpm = 60.0 }]
+
+            [[events]]
+            stop = 0.5
+            sync = "quarter"
+            "#,
+        )
+        .unwrap();
+        let output = render(&sine(2.0), &timeline);
+
+        // 60 BPM の4分音符（1 秒）かけて止まるので、120 BPM なら止まっている時刻でもまだ動いている
+        assert!(max_step(&output, 1.1, 1.3) > 1.0e-3);
+        assert!(max_step(&output, 1.6, 2.0) < 1.0e-3);
+    }
+
+    #[test]
+    fn back_to_back_events_keep_the_restart() {
+        // 1つ目の再始動と同じ時刻に2つ目の停止がある（レガートのノートと同じ）
+        let timeline = Timeline::from_json(
+            r#"{ "events": [
+                { "stop": 0.25, "start": 0.5, "stop_time": 0.1, "start_time": 0.1 },
+                { "stop": 0.5, "start": 1.5, "stop_time": 0.1, "start_time": 0.1 }
+            ] }"#,
+        )
+        .unwrap();
+        let input = sine(2.0);
+        let output = render(&input, &timeline);
+
+        // 止まりきった後、一度再始動してから2つ目の停止に入る
+        assert!(max_step(&output, 0.4, 0.5) < 1.0e-3);
+        assert!(max_step(&output, 0.55, 0.6) > 1.0e-2);
+        assert!(max_step(&output, 1.2, 1.5) < 1.0e-3);
+
+        let tail = to_frame(1.9, SAMPLE_RATE) * 2;
+        assert_eq!(output.samples[tail..], input.samples[tail..]);
+    }
+
+    #[test]
+    fn timeline_rejects_overlapping_events() {
+        let result = Timeline::from_json(r#"{ "events": [ { "stop": 1.0, "start": 2.0 }, { "stop": 1.5 } ] }"#);
+        assert!(matches!(result, Err(RenderError::Timeline(_))));
+
+        let result = Timeline::from_json(r#"{ "events": [ { "stop": 1.0, "start": 0.5 } ] }"#);
+        assert!(matches!(result, Err(RenderError::Timeline(_))));
+    }
+
+    #[test]
+    fn timeline_rejects_negative_stops() {
+        let result = Timeline::from_json(r#"{ "events": [ { "stop": -0.5, "start": 1.0 } ] }"#);
+        assert!(matches!(result, Err(RenderError::Timeline(message)) if message.contains(">= 0")));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,285 @@
+use std::io::{self, BufRead};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use nih_plug::prelude::*;
+
+use crate::plugin::TapeStop;
+use crate::params::TapeStopParams;
+use crate::dsp::engine::{TapeStopEngine, MIN_SYNC_BPM};
+use crate::dsp::oversampling::OversampledEngine;
+use crate::render::{Audio, RenderError};
+
+// nih-plug のスタンドアロンはプラグインを Default で作るので、起動時の設定は static で渡す
+static LOOP_INPUT: OnceLock<LoopInput> = OnceLock::new();
+static KEY_TRIGGER: AtomicBool = AtomicBool::new(false);
+
+const HEADLESS_SAMPLE_RATE: f32 = 48_000.0;
+const HEADLESS_BLOCK: usize = 512;
+
+/**
+ * 入力の代わりにループ再生するWAVファイル
+ */
+pub struct LoopInput {
+    samples: Vec<f32>, // インターリーブされたサンプル（-1.0〜1.0）
+    channels: usize,   // チャンネル数
+    sample_rate: f32,  // ファイルのサンプルレート
+}
+
+impl LoopInput {
+    pub fn open(path: &Path) -> Result<Self, RenderError> {
+        let audio = Audio::read(path)?;
+        if audio.frames() == 0 {
+            return Err(RenderError::Wav(hound::Error::FormatError("the file has no samples")));
+        }
+
+        Ok(Self {
+            samples: audio.samples,
+            channels: audio.channels,
+            sample_rate: audio.sample_rate as f32,
+        })
+    }
+
+    fn frames(&self) -> usize {
+        self.samples.len() / self.channels
+    }
+
+    /**
+     * 再生位置 position（フレーム単位、小数あり）の1フレームを線形補間で読む
+     * モノラルのファイルは全チャンネルに広げ、足りないチャンネルは無音にする
+     */
+    fn read(&self, position: f64, frame: &mut [f32]) {
+        let frames = self.frames();
+        let index = position as usize % frames;
+        let next = (index + 1) % frames;
+        let frac = position.fract() as f32;
+
+        for (ch, out) in frame.iter_mut().enumerate() {
+            let src = if self.channels == 1 { 0 } else { ch };
+            *out = if src < self.channels {
+                let a = self.samples[index * self.channels + src];
+                let b = self.samples[next * self.channels + src];
+                a + (b - a) * frac
+            } else {
+                0.0
+            };
+        }
+    }
+}
+
+/**
+ * ループ入力の再生位置
+ */
+pub(crate) struct LoopPlayer {
+    input: &'static LoopInput,
+    position: f64,
+}
+
+impl LoopPlayer {
+    /**
+     * --loop が指定されていなければ None
+     */
+    pub(crate) fn new() -> Option<Self> {
+        LOOP_INPUT.get().map(|input| Self { input, position: 0.0 })
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.position = 0.0;
+    }
+
+    /**
+     * 1フレーム読み、出力のサンプルレートに合わせて再生位置を進める
+     */
+    pub(crate) fn next(&mut self, sample_rate: f32, frame: &mut [f32]) {
+        self.input.read(self.position, frame);
+        self.position += (self.input.sample_rate / sample_rate) as f64;
+
+        let frames = self.input.frames() as f64;
+        if self.position >= frames {
+            self.position %= frames;
+        }
+    }
+}
+
+/**
+ * キーボード（端末の Enter）でトリガーされているかどうか
+ */
+pub(crate) fn key_trigger() -> bool {
+    KEY_TRIGGER.load(Ordering::Relaxed)
+}
+
+/**
+ * 端末で Enter が押されるたびにトリガーを切り替える
+ */
+fn spawn_key_listener() {
+    thread::spawn(|| {
+        for line in io::stdin().lock().lines() {
+            if line.is_err() {
+                break;
+            }
+            let stopped = !KEY_TRIGGER.fetch_xor(true, Ordering::Relaxed);
+            println!("{}", if stopped { "Tape stop" } else { "Tape start" });
+        }
+    });
+}
+
+/**
+ * ヘッドレスモードの設定
+ */
+struct Headless {
+    output: PathBuf,         // 出力を書き出すWAVファイル
+    seconds: Option<f64>,    // 処理する長さ（None なら Ctrl+C まで）
+}
+
+/**
+ * ウィンドウもオーディオデバイスも使わずに、プラグインと同じパラメータ（既定値）で実時間のペースで処理する
+ * オーディオデバイスのない環境で、ループ入力とキーボードトリガーの結果を確かめるためのもの
+ * 出力は 32bit float のWAVに書き、1秒ごとにヘッダーを更新するので Ctrl+C で止めてもそこまでは読める
+ */
+fn run_headless(headless: &Headless) -> Result<(), RenderError> {
+    let params = TapeStopParams::default();
+    let channels = LOOP_INPUT.get().map_or(2, |input| input.channels);
+    let max_seconds = TapeStopEngine::<f32>::required_seconds(MIN_SYNC_BPM);
+    let factor = params.oversampling.value().factor();
+    let mut engine = OversampledEngine::<f32>::new(HEADLESS_SAMPLE_RATE, max_seconds, channels, factor);
+    let mut player = LoopPlayer::new();
+    let mut input = vec![0.0; channels];
+    let mut output = vec![0.0; channels];
+
+    let spec = hound::WavSpec {
+        channels: channels as u16,
+        sample_rate: HEADLESS_SAMPLE_RATE as u32,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let mut writer = hound::WavWriter::create(&headless.output, spec)?;
+    let total_blocks = headless
+        .seconds
+        .map(|seconds| (seconds * HEADLESS_SAMPLE_RATE as f64 / HEADLESS_BLOCK as f64).ceil() as u64);
+    let flush_every = (HEADLESS_SAMPLE_RATE as usize / HEADLESS_BLOCK).max(1) as u64;
+
+    let block_time = Duration::from_secs_f64(HEADLESS_BLOCK as f64 / HEADLESS_SAMPLE_RATE as f64);
+    let mut deadline = Instant::now();
+    let mut last_state = engine.state();
+    let mut block = 0;
+    while total_blocks.is_none_or(|total| block < total) {
+        // ホストがないのでトランスポートやノートはなく、テンポはプラグインと同じ 120 BPM
+        let engine_params = params.engine_params(params.trigger.value() || key_trigger(), 120.0, FloatParam::value);
+        for _ in 0..HEADLESS_BLOCK {
+            if let Some(player) = &mut player {
+                player.next(HEADLESS_SAMPLE_RATE, &mut input);
+            }
+            engine.process(&input, &mut output, &engine_params);
+            for sample in &output {
+                writer.write_sample(*sample)?;
+            }
+        }
+
+        let state = engine.state();
+        if state != last_state {
+            println!("{state:?}");
+            last_state = state;
+        }
+
+        block += 1;
+        if block % flush_every == 0 {
+            writer.flush()?;
+        }
+
+        deadline += block_time;
+        thread::sleep(deadline.saturating_duration_since(Instant::now()));
+    }
+    writer.finalize()?;
+    Ok(())
+}
+
+/**
+ * スタンドアロン版を起動する
+ * 独自のオプションを取り除き、残りを nih-plug のスタンドアロン（--backend jack/dummy など）に渡す
+ * - --loop <file.wav> 入力の代わりにWAVファイルをループ再生する
+ * - --headless <out.wav> ウィンドウもオーディオデバイスも使わずに処理し、出力をWAVに書き出す
+ * - --seconds <秒> ヘッドレスで処理する長さ（省略すると Ctrl+C まで）
+ */
+pub fn run() -> bool {
+    let mut args = std::env::args();
+    let mut passthrough: Vec<String> = args.next().into_iter().collect();
+    let mut headless = None;
+    let mut seconds = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--loop" => {
+                let Some(path) = args.next() else {
+                    eprintln!("--loop needs a WAV file");
+                    return false;
+                };
+                match LoopInput::open(Path::new(&path)) {
+                    Ok(input) => {
+                        let _ = LOOP_INPUT.set(input);
+                    }
+                    Err(err) => {
+                        eprintln!("Failed to read {path}: {err}");
+                        return false;
+                    }
+                }
+            }
+            "--headless" => {
+                let Some(path) = args.next() else {
+                    eprintln!("--headless needs an output WAV file");
+                    return false;
+                };
+                headless = Some(PathBuf::from(path));
+            }
+            "--seconds" => match args.next().and_then(|value| value.parse::<f64>().ok()) {
+                Some(value) if value > 0.0 => seconds = Some(value),
+                _ => {
+                    eprintln!("--seconds needs a positive number");
+                    return false;
+                }
+            },
+            _ => passthrough.push(arg),
+        }
+    }
+
+    spawn_key_listener();
+    println!("Press Enter to toggle the tape stop");
+
+    if let Some(output) = headless {
+        let headless = Headless { output, seconds };
+        return match run_headless(&headless) {
+            Ok(()) => true,
+            Err(err) => {
+                eprintln!("Failed to write {}: {err}", headless.output.display());
+                false
+            }
+        };
+    }
+    nih_export_standalone_with_args::<TapeStop, _>(passthrough)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loop_player_resamples_and_wraps() {
+        let input: &'static LoopInput = Box::leak(Box::new(LoopInput {
+            samples: vec![0.0, 1.0, 2.0, 3.0],
+            channels: 1,
+            sample_rate: 24_000.0,
+        }));
+        let mut player = LoopPlayer { input, position: 0.0 };
+        let mut frame = [0.0f32; 2];
+
+        // 48 kHz で読むと半サンプルずつ進み、モノラルは両チャンネルに広がる
+        let mut read = Vec::new();
+        for _ in 0..9 {
+            player.next(48_000.0, &mut frame);
+            assert_eq!(frame[0], frame[1]);
+            read.push(frame[0]);
+        }
+        assert_eq!(read, [0.0, 0.5, 1.0, 1.5, 2.0, 2.5, 3.0, 1.5, 0.0]);
+    }
+}
\ No newline at end of file